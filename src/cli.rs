@@ -2,10 +2,25 @@
 
 use std::{io, time::Instant};
 
-use crate::{Inputs, Outputs, frequency_response::Frequencies};
-use clap::Parser;
+use crate::{
+    Inputs, Outputs,
+    compare::CompareArgs,
+    damping::{DampingError, DampingModel, DampingSchedule},
+    data::{Layout, MagnitudeUnit, PhaseUnit, Representation},
+    discovery::{self, Suggestions},
+    frequency_response::{Frequencies, FrequenciesError, FrequencyUnit, Shard},
+    inventory::ListArgs,
+    merge::MergeArgs,
+    selection::{Channels, IoSelection},
+    structural::CachePolicy,
+};
+use clap::{Parser, ValueEnum};
 use nalgebra::DMatrix;
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum CliError {
@@ -17,8 +32,25 @@ pub enum CliError {
     DeserOpticalSensitivities(#[from] bincode::error::DecodeError),
     #[error("")]
     Lom,
+    #[error("failed to load the FEM modal damping")]
+    Damping(#[from] DampingError),
+    #[error(r#"unknown FEM input: "{0}"{1}"#)]
+    UnknownInput(String, Suggestions),
+    #[error(r#"unknown FEM output: "{0}"{1}"#)]
+    UnknownOutput(String, Suggestions),
+    #[error("channels selection of {0} is not supported with the linear optical model outputs")]
+    LomChannels(String),
+    #[error("the adaptive sampling cannot be streamed, checkpointed or sharded")]
+    Adaptive,
+    #[error("failed to evaluate the sampling frequencies")]
+    Frequencies(#[from] FrequenciesError),
 }
 
+/// Linear optical model outputs
+const LOM_OUTPUTS: [&str; 3] = ["tip-tilt", "segment_tip-tilt", "segment_piston"];
+/// FEM outputs the linear optical model outputs are derived from
+const LOM_FEM_OUTPUTS: [&str; 2] = ["OSS_M1_lcl", "MC_M2_lcl_6D"];
+
 /// Linear optical model
 ///
 /// The linear optical model consists of 3 matrices
@@ -47,6 +79,19 @@ impl Lom {
     }
 }
 
+/// CLI commands
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Command {
+    #[command(flatten)]
+    Frequencies(Frequencies),
+    /// lists the FEM inputs and outputs with their number of channels and the FEM eigen frequencies
+    List(ListArgs),
+    /// merges the data files of the shards of a frequency sweep into the data file
+    Merge(MergeArgs),
+    /// compares the frequency responses of two data files
+    Compare(CompareArgs),
+}
+
 /// GMT FEM transfer functions derivation
 #[derive(Parser)]
 #[command(
@@ -57,64 +102,278 @@ impl Lom {
     next_help_heading = "FEM options"
 )]
 pub struct Cli {
-    /// FEM inputs
+    /// FEM inputs, either the command line names or the FEM names,
+    /// optionally followed by a channels selection e.g. `oss-harpoint-delta-f[0..6]`
     #[arg(short, long)]
-    pub inputs: Vec<Inputs>,
-    /// FEM outputs
+    pub inputs: Vec<IoSelection>,
+    /// FEM outputs, either the command line names or the FEM names,
+    /// optionally followed by a channels selection e.g. `ossm1-lcl[0..6,42..48]`
     #[arg(short, long)]
-    pub outputs: Vec<Outputs>,
+    pub outputs: Vec<IoSelection>,
+    /// FEM repository (overrides `FEM_REPO`),
+    /// the inputs and outputs are checked against the inputs and outputs of this FEM
+    #[arg(long)]
+    pub fem: Option<String>,
     /// FEM modal damping coeffcient
     #[arg(short = 'z', long, default_value_t = 0.02f64)]
     pub structural_damping: f64,
+    /// FEM modal damping model, the damping coefficients are either
+    /// the viscous damping ratios or the hysteretic loss factors
+    #[arg(long, value_enum, default_value_t = DampingModel::Viscous)]
+    pub damping_model: DampingModel,
+    /// FEM modal damping bands: table file with rows `lower [Hz], upper [Hz], damping coefficient`,
+    /// the modes outside the bands are damped with the modal damping coefficient
+    #[arg(long, conflicts_with_all = ["modal_damping", "rayleigh_damping"])]
+    pub damping_bands: Option<String>,
+    /// FEM per-mode damping coefficients: table file or Matlab file with the variable `modal_damping`
+    #[arg(long, conflicts_with = "rayleigh_damping")]
+    pub modal_damping: Option<String>,
+    /// FEM Rayleigh damping coefficients: ζ = α/(2ω) + βω/2
+    #[arg(long, num_args = 2, value_names = ["ALPHA", "BETA"])]
+    pub rayleigh_damping: Option<Vec<f64>>,
+    /// modal model file (.mat, .npz or .pkl) used instead of the FEM,
+    /// the inputs and outputs select a subset of the modal model inputs and outputs
+    #[arg(long)]
+    pub modal_model: Option<String>,
     /// FEM minimum eigen frequency (lower frequencies are dropped)
     #[arg(long)]
     pub eigen_frequency_min: Option<f64>,
     /// FEM maximum eigen frequency (higher frequencies are truncated)
     #[arg(long)]
     pub eigen_frequency_max: Option<f64>,
-    /// Frequencies \[Hz\]
+    /// compensates the mismatch between the FEM static and dynamic solution gains
+    #[arg(long)]
+    pub static_gain_mismatch_compensation: bool,
+    /// delay \[s\] applied to the static gain mismatch compensation
+    #[arg(long, requires = "static_gain_mismatch_compensation")]
+    pub static_gain_mismatch_delay: Option<f64>,
+    /// adds the residual static gain of the modes above the eigen frequency window (mode acceleration)
+    #[arg(long, conflicts_with = "static_gain_mismatch_compensation")]
+    pub residual_correction: bool,
+    /// builds the structural model from the FEM without reading or writing the cache
+    #[arg(long, conflicts_with = "clear_cache")]
+    pub no_cache: bool,
+    /// deletes the structural model cache before building it
+    #[arg(long)]
+    pub clear_cache: bool,
+    /// Frequencies or FEM inventory
     #[command(subcommand)]
-    pub frequencies: Frequencies,
-    /// data file, either a Matlab (.mat) or Python pickle (.pkl) file
+    pub command: Command,
+    /// units of the sampling frequencies
+    #[arg(long, value_enum, default_value_t = FrequencyUnit::Hz)]
+    pub frequency_unit: FrequencyUnit,
+    /// data file, either a Matlab (.mat), NumPy (.npz), Python pickle (.pkl),
+    /// Universal File Format (.uff or .unv), Apache Parquet (.parquet) or Arrow IPC (.arrow or .feather) file,
+    /// the Parquet and Arrow IPC files require the `tabular` feature
     #[arg(short, long, default_value_t = String::from("gmt_frequency_response.pkl"))]
     pub filename: String,
+    /// units of the frequencies written to the data file
+    #[arg(long, value_enum, default_value_t = FrequencyUnit::Hz)]
+    pub export_frequency_unit: FrequencyUnit,
+    /// representation of the frequency responses written to the data file:
+    /// magnitude and phase (polar), real and imaginary parts (complex) or both
+    #[arg(long, value_enum, default_value_t = Representation::Polar)]
+    pub representation: Representation,
+    /// units of the magnitude written to the data file
+    #[arg(long, value_enum, default_value_t = MagnitudeUnit::Linear)]
+    pub magnitude_unit: MagnitudeUnit,
+    /// units of the phase written to the data file
+    #[arg(long, value_enum, default_value_t = PhaseUnit::Radian)]
+    pub phase_unit: PhaseUnit,
+    /// unwraps the phase written to the data file along the sorted frequencies
+    #[arg(long)]
+    pub unwrap_phase: bool,
+    /// layout of the frequency responses in the data file: a list of frequency responses (records)
+    /// or a frequency vector and `ny×nu×nf` arrays (columnar)
+    #[arg(long, value_enum, default_value_t = Layout::Records, conflicts_with = "stream")]
+    pub layout: Layout,
+    /// writes the frequency responses to the pickle data file by chunks, as they are computed,
+    /// instead of holding all of them in memory
+    #[arg(long)]
+    pub stream: bool,
+    /// saves the frequency responses by chunks, as they are computed, to a checkpoint file
+    /// next to the data file, the checkpoint file is deleted once the data file is written
+    #[arg(long, conflicts_with = "stream")]
+    pub checkpoint: bool,
+    /// resumes an interrupted sweep from its checkpoint file,
+    /// skipping the frequencies already computed
+    #[arg(long, conflicts_with = "stream")]
+    pub resume: bool,
+    /// computes only the shard `k/N` of the frequencies,
+    /// the frequencies are split into `N` contiguous blocks and the shard is the `k`th block (1 ≤ k ≤ N)
+    #[arg(long)]
+    pub shard: Option<Shard>,
+    /// number of frequencies per chunk of streamed or checkpointed frequency responses
+    #[arg(long, default_value_t = 256)]
+    pub chunk_size: usize,
 }
 
 impl Cli {
+    /// Returns the structural model cache policy
+    pub fn cache_policy(&self) -> CachePolicy {
+        match (self.no_cache, self.clear_cache) {
+            (true, _) => CachePolicy::Bypass,
+            (false, true) => CachePolicy::Clear,
+            (false, false) => CachePolicy::Enabled,
+        }
+    }
+    /// Returns the frequencies of the shard or all of them if there is no shard
+    ///
+    /// The frequencies are converted from the sampling frequencies units to Hz
+    pub fn shard_frequencies(&self, frequencies: &Frequencies) -> Result<Frequencies, CliError> {
+        let frequencies = frequencies.clone().into_hz(self.frequency_unit)?;
+        Ok(match &self.shard {
+            Some(shard) => shard.select(&frequencies.values()?).into(),
+            None => frequencies,
+        })
+    }
+    /// Returns the path to the checkpoint file of the data file
+    pub fn checkpoint_path(&self) -> PathBuf {
+        Path::new(&self.filename).with_extension("checkpoint.pkl")
+    }
+    /// Returns the FEM modal damping schedule
+    pub fn damping_schedule(&self) -> Result<DampingSchedule, CliError> {
+        Ok(
+            match (
+                &self.damping_bands,
+                &self.modal_damping,
+                &self.rayleigh_damping,
+            ) {
+                (Some(path), _, _) => {
+                    DampingSchedule::from_bands_file(self.structural_damping, path)?
+                }
+                (None, Some(path), _) => DampingSchedule::from_modal_file(path)?,
+                (None, None, Some(ab)) => DampingSchedule::rayleigh(ab[0], ab[1]),
+                (None, None, None) => DampingSchedule::Constant(self.structural_damping),
+            },
+        )
+    }
+    /// Checks the arguments and replaces the inputs and outputs with the FEM names
+    ///
+    /// The inputs and outputs are checked against the inputs and outputs of
+    /// the FEM the crate has been compiled with.
+    /// If the FEM repository is given, only the linear optical model outputs are checked here
+    /// and the FEM inputs and outputs are checked when the FEM is loaded,
+    /// as are the inputs and outputs of a modal model.
+    pub fn validate(mut self) -> Result<Self, CliError> {
+        if let Command::Frequencies(frequencies) = &self.command {
+            frequencies.validate()?;
+        }
+        if matches!(
+            self.command,
+            Command::Frequencies(Frequencies::Adaptive { .. })
+        ) && (self.stream || self.checkpoint || self.resume || self.shard.is_some())
+        {
+            return Err(CliError::Adaptive);
+        }
+        if self.modal_model.is_some() || (self.inputs.is_empty() && self.outputs.is_empty()) {
+            return Ok(self);
+        }
+        let fem = self.fem.is_some();
+        let (inputs, outputs): (Vec<_>, Vec<_>) = if fem {
+            (
+                vec![],
+                LOM_OUTPUTS
+                    .iter()
+                    .chain(LOM_FEM_OUTPUTS.iter())
+                    .map(|name| (vec![name.to_string()], name.to_string()))
+                    .collect(),
+            )
+        } else {
+            (
+                Inputs::value_variants()
+                    .iter()
+                    .filter_map(|io| {
+                        io.to_possible_value()
+                            .map(|value| (vec![value.get_name().to_string(), io.name()], io.name()))
+                    })
+                    .collect(),
+                Outputs::value_variants()
+                    .iter()
+                    .filter_map(|io| {
+                        io.to_possible_value()
+                            .map(|value| (vec![value.get_name().to_string(), io.name()], io.name()))
+                    })
+                    .collect(),
+            )
+        };
+        for io in self.inputs.iter_mut() {
+            match discovery::lookup(&io.name, &inputs) {
+                Ok(name) => io.name = name,
+                Err(_) if fem => (),
+                Err(suggestions) => {
+                    return Err(CliError::UnknownInput(io.name.clone(), suggestions));
+                }
+            }
+        }
+        for io in self.outputs.iter_mut() {
+            match discovery::lookup(&io.name, &outputs) {
+                Ok(name) => io.name = name,
+                Err(_) if fem => (),
+                Err(suggestions) => {
+                    return Err(CliError::UnknownOutput(io.name.clone(), suggestions));
+                }
+            }
+        }
+        let lom_channels = self.outputs.iter().find(|io| {
+            io.channels.is_some()
+                && (LOM_OUTPUTS.contains(&io.name.as_str())
+                    || LOM_FEM_OUTPUTS.contains(&io.name.as_str()))
+        });
+        match lom_channels {
+            Some(io) if !self.lom_outputs().is_empty() => {
+                return Err(CliError::LomChannels(io.name.clone()));
+            }
+            _ => (),
+        }
+        Ok(self)
+    }
     /// Returns the names of the FEM inputs
     pub fn fem_inputs(&self) -> Vec<String> {
-        self.inputs.iter().map(|io| io.name()).collect()
+        self.inputs.iter().map(|io| io.name.clone()).collect()
     }
     /// Returns the names of the FEM outputs
     pub fn fem_outputs(&self) -> Vec<String> {
         let mut outs: Vec<_> = self
             .outputs
             .iter()
-            .map(|io| io.name())
-            .filter(|name| {
-                !(name == "tip-tilt" || name == "segment_tip-tilt" || name == "segment_piston")
-            })
+            .map(|io| io.name.clone())
+            .filter(|name| !LOM_OUTPUTS.contains(&name.as_str()))
             .collect();
-        #[cfg(fem)]
-        for output in &self.outputs {
-            if let Outputs::TipTilt | Outputs::SegmentTipTilt | Outputs::SegmentPiston = output {
-                let var = Outputs::OSSM1Lcl;
-                outs.push(var.name());
-                let var = Outputs::MCM2Lcl6D;
-                outs.push(var.name());
-            }
+        if !self.lom_outputs().is_empty() {
+            outs.extend(LOM_FEM_OUTPUTS.map(String::from));
         }
         outs.dedup();
         outs
     }
+    /// Returns the channels selection of the FEM inputs
+    pub fn inputs_channels(&self) -> BTreeMap<String, Channels> {
+        self.inputs
+            .iter()
+            .filter_map(|io| {
+                io.channels
+                    .clone()
+                    .map(|channels| (io.name.clone(), channels))
+            })
+            .collect()
+    }
+    /// Returns the channels selection of the FEM outputs
+    pub fn outputs_channels(&self) -> BTreeMap<String, Channels> {
+        self.outputs
+            .iter()
+            .filter_map(|io| {
+                io.channels
+                    .clone()
+                    .map(|channels| (io.name.clone(), channels))
+            })
+            .collect()
+    }
     /// Returns the names of the linear optical model outputs
     pub fn lom_outputs(&self) -> Vec<String> {
         self.outputs
             .iter()
-            .map(|io| io.name())
-            .filter(|name| {
-                name == "tip-tilt" || name == "segment_tip-tilt" || name == "segment_piston"
-            })
+            .map(|io| io.name.clone())
+            .filter(|name| LOM_OUTPUTS.contains(&name.as_str()))
             .collect()
     }
     /// Loads, concatenates and returns the optical sensivity matrices
@@ -123,8 +382,8 @@ impl Cli {
         let mats: Vec<DMatrix<f64>> = self
             .outputs
             .iter()
-            .map(|output| match output {
-                Outputs::TipTilt => Ok(Some(DMatrix::<f64>::from_column_slice(
+            .map(|output| match output.name.as_str() {
+                "tip-tilt" => Ok(Some(DMatrix::<f64>::from_column_slice(
                     2,
                     84,
                     &{
@@ -138,7 +397,7 @@ impl Cli {
                     .unwrap()
                     .tip_tilt,
                 ))),
-                Outputs::SegmentTipTilt => Ok(Some(DMatrix::<f64>::from_column_slice(
+                "segment_tip-tilt" => Ok(Some(DMatrix::<f64>::from_column_slice(
                     14,
                     84,
                     &{
@@ -152,7 +411,7 @@ impl Cli {
                     .unwrap()
                     .segment_tip_tilt,
                 ))),
-                Outputs::SegmentPiston => Ok(Some(DMatrix::<f64>::from_column_slice(
+                "segment_piston" => Ok(Some(DMatrix::<f64>::from_column_slice(
                     7,
                     84,
                     &{
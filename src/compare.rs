@@ -0,0 +1,508 @@
+//! Transfer functions comparison
+//!
+//! Compares the frequency responses of two data files, e.g. computed with two FEM versions.
+//! Both frequency responses are interpolated onto a common frequency grid and,
+//! for each output and input channels pair, the ratio of the frequency responses,
+//! other over reference, gives the magnitude ratio and the phase difference.
+//! The magnitude peaks of each pair are compared and the pairs with peaks
+//! shifted more than a threshold, or without a peak, are flagged
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use nalgebra::DMatrix;
+use num_complex::Complex;
+use serde::Serialize;
+
+use crate::{
+    data::{FrequencyResponseData, TransferFunctionData, TransferFunctionDataError, wrap_phase},
+    frequency_response::{Frequencies, FrequenciesError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompareError {
+    #[error("failed to read data file {0}")]
+    Read(String, #[source] TransferFunctionDataError),
+    #[error("the {0} of both data files mismatch")]
+    Metadata(&'static str),
+    #[error("frequency response sizes mismatch: {0:?} and {1:?}")]
+    Size((usize, usize), (usize, usize)),
+    #[error("the frequency response at {0}Hz has neither the polar nor the complex representation")]
+    Representation(f64),
+    #[error("the frequency ranges of both data files do not overlap")]
+    Overlap,
+    #[error("invalid comparison frequencies")]
+    Frequencies(#[from] FrequenciesError),
+    #[error("failed to write the comparison summary")]
+    Summary(#[from] io::Error),
+    #[error("failed to write the frequency response difference")]
+    Difference(#[source] TransferFunctionDataError),
+}
+type Result<T> = std::result::Result<T, CompareError>;
+
+/// Compare command arguments
+#[derive(Debug, Clone, clap::Args)]
+pub struct CompareArgs {
+    /// reference data file, either a pickle or a Matlab file
+    pub reference: String,
+    /// data file compared to the reference, either a pickle or a Matlab file
+    pub other: String,
+    /// number of frequencies of the logarithmic common frequency grid,
+    /// the reference frequencies within both frequency ranges are used if not set
+    #[arg(short = 'n', long)]
+    pub samples: Option<usize>,
+    /// magnitude peak frequency shift \[%\] above which a channels pair is flagged
+    #[arg(long, default_value_t = 5.)]
+    pub peak_shift: f64,
+    /// summary table file (.csv)
+    #[arg(long, default_value_t = String::from("gmt_frequency_response_comparison.csv"))]
+    pub summary: String,
+    /// frequency response difference data file, either a Matlab (.mat), NumPy (.npz)
+    /// or Python pickle (.pkl) file
+    #[arg(long, default_value_t = String::from("gmt_frequency_response_difference.pkl"))]
+    pub difference: String,
+}
+impl CompareArgs {
+    /// Compares the data files and writes the summary table and the frequency response difference
+    pub fn run(&self) -> Result<()> {
+        let load = |path: &String| {
+            TransferFunctionData::load(path).map_err(|e| CompareError::Read(path.clone(), e))
+        };
+        let reference = load(&self.reference)?;
+        let comparison = Comparison::new(&reference, &load(&self.other)?, self)?;
+        println!("{comparison}");
+        comparison.write_summary(&self.summary)?;
+        println!("Comparison summary written to {}", self.summary);
+        comparison
+            .difference(reference)
+            .dump(&self.difference)
+            .map_err(CompareError::Difference)?;
+        Ok(())
+    }
+}
+
+/// Complex frequency responses sorted by increasing frequencies
+struct Responses {
+    frequencies: Vec<f64>,
+    matrices: Vec<DMatrix<Complex<f64>>>,
+}
+impl Responses {
+    fn new(data: &TransferFunctionData) -> Result<Self> {
+        let mut records = data.frequency_response().into_owned();
+        records.sort_by(|a, b| a.frequency().total_cmp(&b.frequency()));
+        let matrices = records
+            .iter()
+            .map(|data| {
+                data.complex_matrix()
+                    .ok_or(CompareError::Representation(data.frequency()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            frequencies: records.iter().map(|data| data.frequency()).collect(),
+            matrices,
+        })
+    }
+    /// Returns the size of the frequency response matrices
+    fn size(&self) -> Option<(usize, usize)> {
+        self.matrices.first().map(|m| m.shape())
+    }
+    /// Returns the frequency range \[Hz\]
+    fn range(&self) -> Option<(f64, f64)> {
+        self.frequencies
+            .first()
+            .copied()
+            .zip(self.frequencies.last().copied())
+    }
+    /// Interpolates the frequency response at the frequency `nu` \[Hz\]
+    ///
+    /// The magnitude is interpolated in logarithmic scale and the phase along the shortest arc
+    fn interpolate(&self, nu: f64) -> DMatrix<Complex<f64>> {
+        let k = self.frequencies.partition_point(|&x| x < nu);
+        if k == 0 {
+            return self.matrices[0].clone();
+        }
+        if k == self.frequencies.len() {
+            return self.matrices[k - 1].clone();
+        }
+        let (a, b) = (self.frequencies[k - 1], self.frequencies[k]);
+        if b == nu {
+            return self.matrices[k].clone();
+        }
+        let t = (nu - a) / (b - a);
+        self.matrices[k - 1].zip_map(&self.matrices[k], |a, b| interpolate(a, b, t))
+    }
+    /// Returns the frequency of the largest magnitude of the output channel `i` to the input channel `j`
+    /// within the frequency range \[Hz\]
+    ///
+    /// Returns `None` if there is no finite magnitude within the frequency range
+    fn peak(&self, i: usize, j: usize, (lower, upper): (f64, f64)) -> Option<f64> {
+        self.frequencies
+            .iter()
+            .zip(&self.matrices)
+            .filter(|(nu, _)| (lower..=upper).contains(*nu))
+            .map(|(nu, m)| (*nu, m[(i, j)].norm()))
+            .filter(|(_, x)| x.is_finite())
+            .fold(None, |peak: Option<(f64, f64)>, (nu, x)| match peak {
+                Some((_, y)) if y >= x => peak,
+                _ => Some((nu, x)),
+            })
+            .map(|(nu, _)| nu)
+    }
+}
+
+/// Interpolates between the complex values `a` and `b`, `t` is in the range `[0,1]`
+fn interpolate(a: Complex<f64>, b: Complex<f64>, t: f64) -> Complex<f64> {
+    let ((ma, pa), (mb, pb)) = (a.to_polar(), b.to_polar());
+    if ma == 0. || mb == 0. {
+        return a + (b - a) * t;
+    }
+    Complex::from_polar(ma * (mb / ma).powf(t), pa + t * wrap_phase(pb - pa))
+}
+
+/// Comparison of the frequency responses of an output channel to an input channel
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelComparison {
+    /// output channel index
+    pub output: usize,
+    /// input channel index
+    pub input: usize,
+    /// FEM output name and channel index
+    pub output_channel: (String, usize),
+    /// FEM input name and channel index
+    pub input_channel: (String, usize),
+    /// largest magnitude ratio, other over reference, \[dB\]
+    pub magnitude_ratio: f64,
+    /// frequency of the largest magnitude ratio \[Hz\]
+    pub magnitude_ratio_frequency: f64,
+    /// largest phase difference, other minus reference, \[deg\]
+    pub phase_difference: f64,
+    /// frequency of the largest phase difference \[Hz\]
+    pub phase_difference_frequency: f64,
+    /// reference magnitude peak frequency \[Hz\], if any
+    pub reference_peak: Option<f64>,
+    /// other magnitude peak frequency \[Hz\], if any
+    pub peak: Option<f64>,
+    /// magnitude peak frequency shift relative to the reference \[%\],
+    /// if both peaks are found and the reference peak frequency is not zero
+    pub peak_shift: Option<f64>,
+    /// peak frequency shift above the threshold or undefined
+    pub flagged: bool,
+}
+
+impl ChannelComparison {
+    /// Returns the output and input channels pair as `output #i name[channel], input #j name[channel]`
+    fn pair(&self) -> String {
+        format!(
+            "output #{} {}[{}], input #{} {}[{}]",
+            self.output,
+            self.output_channel.0,
+            self.output_channel.1,
+            self.input,
+            self.input_channel.0,
+            self.input_channel.1
+        )
+    }
+}
+
+/// Comparison of two transfer functions data
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// common frequencies \[Hz\]
+    pub frequencies: Vec<f64>,
+    /// ratios of the frequency responses, other over reference, at the common frequencies
+    pub ratios: Vec<DMatrix<Complex<f64>>>,
+    /// comparison of each output and input channels pair
+    pub channels: Vec<ChannelComparison>,
+    /// peak frequency shift threshold \[%\]
+    pub peak_shift: f64,
+}
+impl Comparison {
+    /// Compares the frequency responses of `other` to the ones of `reference`
+    pub fn new(
+        reference: &TransferFunctionData,
+        other: &TransferFunctionData,
+        args: &CompareArgs,
+    ) -> Result<Self> {
+        if reference.inputs() != other.inputs() {
+            return Err(CompareError::Metadata("inputs"));
+        }
+        if reference.outputs() != other.outputs() {
+            return Err(CompareError::Metadata("outputs"));
+        }
+        let (a, b) = (Responses::new(reference)?, Responses::new(other)?);
+        let (Some(size), Some(other_size)) = (a.size(), b.size()) else {
+            return Err(CompareError::Overlap);
+        };
+        if size != other_size {
+            return Err(CompareError::Size(size, other_size));
+        }
+        let range = a
+            .range()
+            .zip(b.range())
+            .map(|((a_min, a_max), (b_min, b_max))| (a_min.max(b_min), a_max.min(b_max)))
+            .filter(|(lower, upper)| lower <= upper)
+            .ok_or(CompareError::Overlap)?;
+        let frequencies = match args.samples {
+            Some(n) => Frequencies::logspace(range.0, range.1, n).values()?,
+            None => a
+                .frequencies
+                .iter()
+                .copied()
+                .filter(|nu| (range.0..=range.1).contains(nu))
+                .collect(),
+        };
+        let ratios: Vec<_> = frequencies
+            .iter()
+            .map(|&nu| b.interpolate(nu).component_div(&a.interpolate(nu)))
+            .collect();
+        let (ny, nu) = size;
+        let (outputs, inputs) = reference.channels();
+        let mut channels = vec![];
+        for j in 0..nu {
+            for i in 0..ny {
+                let largest = |value: fn(Complex<f64>) -> f64| {
+                    frequencies
+                        .iter()
+                        .zip(&ratios)
+                        .map(|(nu, ratio)| (*nu, value(ratio[(i, j)])))
+                        .filter(|(_, x)| x.is_finite())
+                        .fold((f64::NAN, f64::NAN), |largest, (nu, x)| {
+                            if largest.1.is_nan() || x.abs() > largest.1.abs() {
+                                (nu, x)
+                            } else {
+                                largest
+                            }
+                        })
+                };
+                let (magnitude_ratio_frequency, magnitude_ratio) =
+                    largest(|x| 20. * x.norm().log10());
+                let (phase_difference_frequency, phase_difference) =
+                    largest(|x| x.arg().to_degrees());
+                let (reference_peak, peak) = (a.peak(i, j, range), b.peak(i, j, range));
+                let peak_shift = reference_peak
+                    .zip(peak)
+                    .map(|(reference_peak, peak)| 100. * (peak - reference_peak) / reference_peak)
+                    .filter(|peak_shift| peak_shift.is_finite());
+                channels.push(ChannelComparison {
+                    output: i,
+                    input: j,
+                    output_channel: outputs[i].clone(),
+                    input_channel: inputs[j].clone(),
+                    magnitude_ratio,
+                    magnitude_ratio_frequency,
+                    phase_difference,
+                    phase_difference_frequency,
+                    reference_peak,
+                    peak,
+                    peak_shift,
+                    flagged: peak_shift.is_none_or(|peak_shift| peak_shift.abs() > args.peak_shift),
+                });
+            }
+        }
+        Ok(Self {
+            frequencies,
+            ratios,
+            channels,
+            peak_shift: args.peak_shift,
+        })
+    }
+    /// Writes the comparison of each output and input channels pair to a CSV file
+    ///
+    /// The undefined peaks and peak shifts are left empty
+    pub fn write_summary(&self, path: impl AsRef<Path>) -> Result<()> {
+        let optional = |x: Option<f64>| x.map(|x| x.to_string()).unwrap_or_default();
+        let mut buffer = BufWriter::new(File::create(path)?);
+        writeln!(
+            buffer,
+            "output,input,output_name,output_channel,input_name,input_channel,magnitude_ratio[dB],magnitude_ratio_frequency[Hz],phase_difference[deg],phase_difference_frequency[Hz],reference_peak[Hz],peak[Hz],peak_shift[%],flagged"
+        )?;
+        for c in &self.channels {
+            writeln!(
+                buffer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                c.output,
+                c.input,
+                c.output_channel.0,
+                c.output_channel.1,
+                c.input_channel.0,
+                c.input_channel.1,
+                c.magnitude_ratio,
+                c.magnitude_ratio_frequency,
+                c.phase_difference,
+                c.phase_difference_frequency,
+                optional(c.reference_peak),
+                optional(c.peak),
+                optional(c.peak_shift),
+                c.flagged
+            )?;
+        }
+        buffer.flush()?;
+        Ok(())
+    }
+    /// Returns the reference data with the ratios of the frequency responses, other over reference,
+    /// as frequency response
+    pub fn difference(&self, reference: TransferFunctionData) -> TransferFunctionData {
+        reference.shard(None).add_response(
+            self.frequencies
+                .iter()
+                .zip(&self.ratios)
+                .map(|(nu, ratio)| FrequencyResponseData::new(*nu, ratio.clone()))
+                .collect(),
+        )
+    }
+}
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} channels pairs compared at {} frequencies",
+            self.channels.len(),
+            self.frequencies.len()
+        )?;
+        if let (Some(lower), Some(upper)) = (self.frequencies.first(), self.frequencies.last()) {
+            write!(f, " in [{lower:.3},{upper:.3}]Hz")?;
+        }
+        writeln!(f)?;
+        let largest = |value: fn(&ChannelComparison) -> f64| {
+            self.channels
+                .iter()
+                .filter(|c| value(c).is_finite())
+                .max_by(|a, b| value(a).abs().total_cmp(&value(b).abs()))
+        };
+        if let Some(c) = largest(|c| c.magnitude_ratio) {
+            writeln!(
+                f,
+                " largest magnitude ratio : {:+9.3}dB  ({} @ {:.3}Hz)",
+                c.magnitude_ratio,
+                c.pair(),
+                c.magnitude_ratio_frequency
+            )?;
+        }
+        if let Some(c) = largest(|c| c.phase_difference) {
+            writeln!(
+                f,
+                " largest phase difference: {:+9.3}deg ({} @ {:.3}Hz)",
+                c.phase_difference,
+                c.pair(),
+                c.phase_difference_frequency
+            )?;
+        }
+        let flagged: Vec<_> = self.channels.iter().filter(|c| c.flagged).collect();
+        write!(
+            f,
+            " {} channels pairs with a peak shift larger than {}% or without a peak",
+            flagged.len(),
+            self.peak_shift
+        )?;
+        for c in flagged {
+            match (c.reference_peak, c.peak, c.peak_shift) {
+                (Some(reference_peak), Some(peak), Some(peak_shift)) => write!(
+                    f,
+                    "\n  {}: {:.3}Hz -> {:.3}Hz ({:+.2}%)",
+                    c.pair(),
+                    reference_peak,
+                    peak,
+                    peak_shift
+                )?,
+                (reference_peak, peak, _) => {
+                    let hz = |x: Option<f64>| x.map_or("none".to_string(), |x| format!("{x:.3}Hz"));
+                    write!(
+                        f,
+                        "\n  {}: undefined peak shift ({} -> {})",
+                        c.pair(),
+                        hz(reference_peak),
+                        hz(peak)
+                    )?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FrequencyResponseVec;
+
+    fn data(eigen_frequency: f64) -> TransferFunctionData {
+        let w = 2. * std::f64::consts::PI * eigen_frequency;
+        let records: FrequencyResponseVec<DMatrix<Complex<f64>>> = (1..=200)
+            .map(|k| {
+                let nu = k as f64 * 0.1;
+                let jw = Complex::new(0., 2. * std::f64::consts::PI * nu);
+                let h = 1. / (w * w + 2. * 0.02 * w * jw + jw * jw);
+                FrequencyResponseData::new(nu, DMatrix::from_element(1, 2, h))
+            })
+            .collect();
+        TransferFunctionData::default().add_response(records)
+    }
+
+    fn args() -> CompareArgs {
+        CompareArgs {
+            reference: String::new(),
+            other: String::new(),
+            samples: None,
+            peak_shift: 5.,
+            summary: String::new(),
+            difference: String::new(),
+        }
+    }
+
+    #[test]
+    fn identical() {
+        let comparison = Comparison::new(&data(10.), &data(10.), &args()).unwrap();
+        assert_eq!(comparison.channels.len(), 2);
+        let c = &comparison.channels[1];
+        assert_eq!((c.output, c.input), (0, 1));
+        assert_eq!(c.input_channel, ("input".to_string(), 1));
+        assert!(c.magnitude_ratio.abs() < 1e-9 && c.phase_difference.abs() < 1e-9);
+        assert!(!c.flagged);
+    }
+
+    #[test]
+    fn peak_shift() {
+        let comparison = Comparison::new(
+            &data(10.),
+            &data(11.),
+            &CompareArgs {
+                samples: Some(100),
+                ..args()
+            },
+        )
+        .unwrap();
+        assert_eq!(comparison.frequencies.len(), 100);
+        let c = &comparison.channels[0];
+        let (reference_peak, peak) = (c.reference_peak.unwrap(), c.peak.unwrap());
+        assert!((reference_peak - 10.).abs() < 0.11 && (peak - 11.).abs() < 0.11);
+        assert!(c.flagged);
+        let difference = comparison.difference(data(10.));
+        assert_eq!(difference.frequencies(), comparison.frequencies);
+    }
+
+    #[test]
+    fn undefined_peak() {
+        // magnitude peaks at 0Hz
+        let records: FrequencyResponseVec<DMatrix<Complex<f64>>> = [0., 1., 2.]
+            .into_iter()
+            .map(|nu| {
+                FrequencyResponseData::new(
+                    nu,
+                    DMatrix::from_element(1, 1, Complex::new(3. - nu, 0.)),
+                )
+            })
+            .collect();
+        let data = TransferFunctionData::default().add_response(records);
+        let comparison = Comparison::new(&data, &data, &args()).unwrap();
+        let c = &comparison.channels[0];
+        assert_eq!(
+            (c.reference_peak, c.peak, c.peak_shift),
+            (Some(0.), Some(0.), None)
+        );
+        assert!(c.flagged);
+    }
+}
@@ -0,0 +1,233 @@
+//! FEM modal damping schedules
+
+use std::{f64::consts, fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::table::{self, TableError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DampingError {
+    #[error("failed to load damping table")]
+    Table(#[from] TableError),
+    #[error("failed to load damping from Matlab file")]
+    Matlab(#[from] matio_rs::MatioError),
+    #[error("damping band #{0} must have 3 values: lower [Hz], upper [Hz] and damping coefficient")]
+    Band(usize),
+    #[error("expected at least {0} modal damping coefficients, found {1}")]
+    ModalLength(usize, usize),
+}
+type Result<T> = std::result::Result<T, DampingError>;
+
+/// Modal damping model
+///
+/// The damping coefficients of the [DampingSchedule] are either
+/// the viscous damping ratios `ζ` or the hysteretic loss factors `η`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DampingModel {
+    /// viscous damping: `ω² + 2ζωjω + (jω)²`
+    #[default]
+    Viscous,
+    /// hysteretic (structural) damping: `ω²(1 + jη) + (jω)²`
+    Hysteretic,
+}
+impl DampingModel {
+    /// Returns the name of the damping model
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Viscous => "viscous",
+            Self::Hysteretic => "hysteretic",
+        }
+    }
+}
+impl Display for DampingModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Modal damping coefficient of the modes within a frequency band
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DampingBand {
+    /// band lower frequency (inclusive) \[Hz\]
+    pub lower: f64,
+    /// band upper frequency (exclusive) \[Hz\]
+    pub upper: f64,
+    /// damping coefficient
+    pub z: f64,
+}
+
+/// Modal damping schedule
+///
+/// The schedule gives the damping coefficient of each FEM mode
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum DampingSchedule {
+    /// the same damping coefficient for all the modes
+    Constant(f64),
+    /// the damping coefficient of the first band the eigen frequency belongs to,
+    /// or the default damping coefficient if the eigen frequency is outside all the bands
+    Bands {
+        default: f64,
+        bands: Vec<DampingBand>,
+    },
+    /// the damping coefficient of each FEM mode
+    Modal(Vec<f64>),
+    /// Rayleigh damping: `ζ = α/(2ω) + βω/2`
+    Rayleigh { alpha: f64, beta: f64 },
+}
+impl Default for DampingSchedule {
+    fn default() -> Self {
+        Self::Constant(2e-2)
+    }
+}
+impl From<f64> for DampingSchedule {
+    fn from(value: f64) -> Self {
+        Self::Constant(value)
+    }
+}
+impl From<Vec<f64>> for DampingSchedule {
+    fn from(value: Vec<f64>) -> Self {
+        Self::Modal(value)
+    }
+}
+impl DampingSchedule {
+    /// Creates a banded schedule from a table with rows: lower \[Hz\], upper \[Hz\], damping coefficient
+    ///
+    /// Modes outside the bands are damped with the `default` coefficient
+    pub fn from_bands_file(default: f64, path: impl AsRef<Path>) -> Result<Self> {
+        let bands = table::read_rows(path)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| match row.as_slice() {
+                &[lower, upper, z] => Ok(DampingBand { lower, upper, z }),
+                _ => Err(DampingError::Band(i)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::Bands { default, bands })
+    }
+    /// Creates a modal schedule from either a text table or a Matlab file
+    ///
+    /// The Matlab file must contain the variable `modal_damping`
+    pub fn from_modal_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let z: Vec<f64> = match path.extension() {
+            Some(ext) if ext == "mat" => matio_rs::MatFile::load(path)?.var("modal_damping")?,
+            _ => table::read_values(path)?,
+        };
+        Ok(Self::Modal(z))
+    }
+    /// Creates a Rayleigh schedule
+    pub fn rayleigh(alpha: f64, beta: f64) -> Self {
+        Self::Rayleigh { alpha, beta }
+    }
+    /// Returns the damping coefficient of each mode given the eigen frequencies \[rd/s\]
+    ///
+    /// Rigid body modes (`w=0`) are not damped with the Rayleigh schedule
+    pub fn modal_coefficients(&self, w: &[f64]) -> Result<Vec<f64>> {
+        Ok(match self {
+            Self::Constant(z) => vec![*z; w.len()],
+            Self::Bands { default, bands } => w
+                .iter()
+                .map(|wi| wi * 0.5 * consts::FRAC_1_PI)
+                .map(|f| {
+                    bands
+                        .iter()
+                        .find(|band| f >= band.lower && f < band.upper)
+                        .map_or(*default, |band| band.z)
+                })
+                .collect(),
+            Self::Modal(z) => {
+                if z.len() < w.len() {
+                    return Err(DampingError::ModalLength(w.len(), z.len()));
+                }
+                z[..w.len()].to_vec()
+            }
+            Self::Rayleigh { alpha, beta } => w
+                .iter()
+                .map(|wi| {
+                    if *wi > 0f64 {
+                        0.5 * (alpha / wi + beta * wi)
+                    } else {
+                        0f64
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+impl Display for DampingSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Constant(z) => write!(f, "{:}%", z * 1e2),
+            Self::Bands { default, bands } => {
+                write!(f, "{:}%", default * 1e2)?;
+                for band in bands {
+                    write!(
+                        f,
+                        ", [{:.3},{:.3}[Hz: {:}%",
+                        band.lower,
+                        band.upper,
+                        band.z * 1e2
+                    )?;
+                }
+                Ok(())
+            }
+            Self::Modal(z) => {
+                let (min, max) = z
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), z| {
+                        (min.min(*z), max.max(*z))
+                    });
+                write!(f, "modal [{:},{:}]%", min * 1e2, max * 1e2)
+            }
+            Self::Rayleigh { alpha, beta } => write!(f, "Rayleigh (α={alpha:e},β={beta:e})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands() {
+        let damping = DampingSchedule::Bands {
+            default: 0.02,
+            bands: vec![
+                DampingBand {
+                    lower: 0.,
+                    upper: 10.,
+                    z: 0.01,
+                },
+                DampingBand {
+                    lower: 10.,
+                    upper: 100.,
+                    z: 0.005,
+                },
+            ],
+        };
+        let w: Vec<_> = [1., 20., 50., 200.]
+            .into_iter()
+            .map(|f: f64| f * 2. * consts::PI)
+            .collect();
+        assert_eq!(
+            damping.modal_coefficients(&w).unwrap(),
+            vec![0.01, 0.005, 0.005, 0.02]
+        );
+    }
+
+    #[test]
+    fn rayleigh() {
+        let damping = DampingSchedule::rayleigh(2., 0.5);
+        let z = damping.modal_coefficients(&[0., 2.]).unwrap();
+        assert_eq!(z, vec![0., 1.]);
+    }
+
+    #[test]
+    fn modal_length() {
+        let damping = DampingSchedule::from(vec![0.01; 2]);
+        assert!(damping.modal_coefficients(&[1., 2., 3.]).is_err());
+    }
+}
@@ -1,25 +1,53 @@
 //! Frequency response data products
 
 use nalgebra::{Complex, ComplexField, DMatrix};
-use serde::Serialize;
-use std::io::BufWriter;
+use serde::{Deserialize, Serialize};
+use std::io::{BufWriter, Write};
 use std::time::Instant;
-use std::{env, f64, fmt::Display, fs::File, io, ops::Deref, path::Path};
+use std::{borrow::Cow, f64, fmt::Display, fs::File, io, ops::Deref, path::Path};
 
-use crate::{cli::Cli, structural::Structural};
+#[cfg(feature = "tabular")]
+use crate::tabular::{self, Row, TableFormat, TabularError};
+use crate::{
+    cli::Cli,
+    damping::{DampingBand, DampingModel, DampingSchedule},
+    discovery::FemIo,
+    frequency_response::{FrequencyUnit, Shard},
+    stream::{self, StreamError},
+    structural::{Structural, StructuralCacheKey},
+    uff::{self, FrequencyResponseFunction},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum TransferFunctionDataError {
-    #[error(r#"found data file extension: "{0}", expected "mat" or "pkl""#)]
-    DataFileExtension(String),
-    #[error(r#"missing data file extension: "mat" or "pkl""#)]
-    MissingFileExtension,
+    #[error(r#"found data file extension: "{0}", expected {1}"#)]
+    DataFileExtension(String, &'static str),
+    #[error("missing data file extension: {0}")]
+    MissingFileExtension(&'static str),
     #[error("failed to create data file: {0}")]
     CreateDataFile(#[from] io::Error),
     #[error("failed to serialize data to pickle file")]
     SerPkl(#[from] serde_pickle::Error),
-    #[error("failed to write to Matlab data file")]
+    #[error("failed to read or write Matlab data file")]
     Matlab(#[from] matio_rs::MatioError),
+    #[error("transfer functions {0} mismatch")]
+    Metadata(&'static str),
+    #[error("invalid shards: {0}")]
+    Shards(String),
+    #[error("failed to read pickle data file")]
+    ReadPkl(#[source] Box<StreamError>),
+    #[error("failed to read or write the strings of Matlab data file")]
+    MatlabStrings(#[from] serde_json::Error),
+    #[error(r#"invalid {0} "{1}" in Matlab data file"#)]
+    MatlabValue(&'static str, String),
+    #[error("the frequency response at {0}Hz has neither the polar nor the complex representation")]
+    Representation(f64),
+    #[cfg(feature = "tabular")]
+    #[error("failed to write tabular data file")]
+    Tabular(#[from] TabularError),
+    #[cfg(not(feature = "tabular"))]
+    #[error(r#"writing "{0}" data files requires the "tabular" feature"#)]
+    TabularFeature(String),
 }
 
 type Result<T> = std::result::Result<T, TransferFunctionDataError>;
@@ -46,11 +74,74 @@ impl Dims for f64 {
     }
 }
 
+/// Midpoint interpolation error interface
+///
+/// The errors are the largest differences between the value at the middle of an interval
+/// and the interpolation of the values at both ends of the interval
+pub trait MidpointError {
+    /// Returns the magnitude interpolation error \[dB\]
+    fn magnitude_error(a: &Self, m: &Self, b: &Self) -> f64;
+    /// Returns the phase interpolation error \[rd\]
+    fn phase_error(a: &Self, m: &Self, b: &Self) -> f64;
+}
+
+// Magnitude interpolation error [dB]
+fn magnitude_error(a: f64, m: f64, b: f64) -> f64 {
+    let db = |x: f64| 20. * x.max(f64::MIN_POSITIVE).log10();
+    (db(m) - 0.5 * (db(a) + db(b))).abs()
+}
+// Phase wrapped to [-π,π[ [rd]
+pub(crate) fn wrap_phase(x: f64) -> f64 {
+    (x + f64::consts::PI).rem_euclid(2. * f64::consts::PI) - f64::consts::PI
+}
+// Phase interpolation error [rd]
+fn phase_error(a: f64, m: f64, b: f64) -> f64 {
+    wrap_phase(m - (a + 0.5 * wrap_phase(b - a))).abs()
+}
+
+impl MidpointError for f64 {
+    fn magnitude_error(a: &Self, m: &Self, b: &Self) -> f64 {
+        magnitude_error(*a, *m, *b)
+    }
+
+    fn phase_error(a: &Self, m: &Self, b: &Self) -> f64 {
+        phase_error(*a, *m, *b)
+    }
+}
+
+impl MidpointError for DMatrix<f64> {
+    fn magnitude_error(a: &Self, m: &Self, b: &Self) -> f64 {
+        a.iter()
+            .zip(m.iter())
+            .zip(b.iter())
+            .map(|((a, m), b)| magnitude_error(*a, *m, *b))
+            .fold(0., f64::max)
+    }
+
+    fn phase_error(a: &Self, m: &Self, b: &Self) -> f64 {
+        a.iter()
+            .zip(m.iter())
+            .zip(b.iter())
+            .map(|((a, m), b)| phase_error(*a, *m, *b))
+            .fold(0., f64::max)
+    }
+}
+
 /// Cartesian to polar transformation interface
+///
+/// The complex values are decomposed into either their magnitude and phase
+/// or their real and imaginary parts
 pub trait Cartesian2Polar {
-    type Output: Dims + std::fmt::Debug + Serialize;
+    type Output: Dims + MidpointError + Clone + std::fmt::Debug + Serialize;
     fn magnitude(&self) -> Self::Output;
     fn phase(&self) -> Self::Output;
+    fn real(&self) -> Self::Output;
+    fn imaginary(&self) -> Self::Output;
+    /// Returns the magnitude and the phase \[rd\] of the complex values given by their real and imaginary parts
+    fn to_polar(real: &Self::Output, imaginary: &Self::Output) -> (Self::Output, Self::Output);
+    /// Returns the real and imaginary parts of the complex values given by their magnitude and phase \[rd\]
+    fn to_cartesian(magnitude: &Self::Output, phase: &Self::Output)
+    -> (Self::Output, Self::Output);
 }
 
 impl Cartesian2Polar for DMatrix<Complex<f64>> {
@@ -63,6 +154,33 @@ impl Cartesian2Polar for DMatrix<Complex<f64>> {
     fn phase(&self) -> Self::Output {
         self.map(|x| x.argument())
     }
+
+    fn real(&self) -> Self::Output {
+        self.map(|x| x.re)
+    }
+
+    fn imaginary(&self) -> Self::Output {
+        self.map(|x| x.im)
+    }
+
+    fn to_polar(real: &Self::Output, imaginary: &Self::Output) -> (Self::Output, Self::Output) {
+        let complex = real.zip_map(imaginary, Complex::new);
+        (
+            Cartesian2Polar::magnitude(&complex),
+            Cartesian2Polar::phase(&complex),
+        )
+    }
+
+    fn to_cartesian(
+        magnitude: &Self::Output,
+        phase: &Self::Output,
+    ) -> (Self::Output, Self::Output) {
+        let complex = magnitude.zip_map(phase, Complex::from_polar);
+        (
+            Cartesian2Polar::real(&complex),
+            Cartesian2Polar::imaginary(&complex),
+        )
+    }
 }
 
 impl Cartesian2Polar for Complex<f64> {
@@ -75,25 +193,324 @@ impl Cartesian2Polar for Complex<f64> {
     fn phase(&self) -> Self::Output {
         self.argument()
     }
+
+    fn real(&self) -> Self::Output {
+        self.re
+    }
+
+    fn imaginary(&self) -> Self::Output {
+        self.im
+    }
+
+    fn to_polar(real: &Self::Output, imaginary: &Self::Output) -> (Self::Output, Self::Output) {
+        let complex = Complex::new(*real, *imaginary);
+        (complex.modulus(), complex.argument())
+    }
+
+    fn to_cartesian(
+        magnitude: &Self::Output,
+        phase: &Self::Output,
+    ) -> (Self::Output, Self::Output) {
+        let complex = Complex::from_polar(*magnitude, *phase);
+        (complex.re, complex.im)
+    }
+}
+
+/// Frequency response representation in the data files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Representation {
+    /// magnitude and phase
+    #[default]
+    Polar,
+    /// real and imaginary parts
+    Complex,
+    /// magnitude, phase, real and imaginary parts
+    Both,
+}
+impl Representation {
+    /// Returns the name of the representation
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Polar => "polar",
+            Self::Complex => "complex",
+            Self::Both => "both",
+        }
+    }
+}
+impl Display for Representation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Frequency response magnitude units in the data files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+pub enum MagnitudeUnit {
+    /// linear magnitude
+    #[default]
+    #[serde(rename = "linear")]
+    Linear,
+    /// magnitude in decibels: `20log10(|x|)`
+    #[serde(rename = "dB")]
+    #[value(name = "db")]
+    Decibel,
+}
+impl MagnitudeUnit {
+    /// Returns the unit symbol
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Linear => "linear",
+            Self::Decibel => "dB",
+        }
+    }
+    /// Converts a linear magnitude to this unit
+    pub fn linear_to(&self, x: f64) -> f64 {
+        match self {
+            Self::Linear => x,
+            Self::Decibel => 20. * x.log10(),
+        }
+    }
+    /// Converts a magnitude in this unit to a linear magnitude
+    pub fn to_linear(&self, x: f64) -> f64 {
+        match self {
+            Self::Linear => x,
+            Self::Decibel => 10f64.powf(x / 20.),
+        }
+    }
+}
+
+/// Frequency response phase units in the data files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+pub enum PhaseUnit {
+    /// radians
+    #[default]
+    #[serde(rename = "rad")]
+    #[value(name = "rad")]
+    Radian,
+    /// degrees
+    #[serde(rename = "deg")]
+    #[value(name = "deg")]
+    Degree,
+}
+impl PhaseUnit {
+    /// Returns the unit symbol
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Radian => "rad",
+            Self::Degree => "deg",
+        }
+    }
+    /// Converts a phase in radians to this unit
+    pub fn radian_to(&self, x: f64) -> f64 {
+        match self {
+            Self::Radian => x,
+            Self::Degree => x.to_degrees(),
+        }
+    }
+    /// Converts a phase in this unit to radians
+    pub fn to_radian(&self, x: f64) -> f64 {
+        match self {
+            Self::Radian => x,
+            Self::Degree => x.to_radians(),
+        }
+    }
 }
 
 /// Frequency response data point
 ///
-/// Frequency response magnitude and phase matrices at one frequency
-#[derive(Debug, Serialize)]
+/// Frequency response magnitude and phase and/or real and imaginary parts matrices at one frequency
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound(deserialize = "<T as Cartesian2Polar>::Output: Deserialize<'de>"))]
 pub struct FrequencyResponseData<T: Cartesian2Polar> {
     frequency: f64,
-    magnitude: <T as Cartesian2Polar>::Output,
-    phase: <T as Cartesian2Polar>::Output,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    magnitude: Option<<T as Cartesian2Polar>::Output>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    phase: Option<<T as Cartesian2Polar>::Output>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    real: Option<<T as Cartesian2Polar>::Output>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    imaginary: Option<<T as Cartesian2Polar>::Output>,
 }
 impl<T: Cartesian2Polar> FrequencyResponseData<T> {
     /// Creates a [FrequencyResponseData] instance from a frequency and response complex matrix
+    ///
+    /// Only the complex representation is kept,
+    /// the polar representation is derived from it with [FrequencyResponseData::into_representation]
     pub fn new(frequency: f64, response: T) -> Self {
         Self {
             frequency,
-            magnitude: response.magnitude(),
-            phase: response.phase(),
+            magnitude: None,
+            phase: None,
+            real: Some(response.real()),
+            imaginary: Some(response.imaginary()),
+        }
+    }
+    /// Keeps only the frequency response components of the `representation`
+    ///
+    /// The missing components are derived from the other representation,
+    /// with the magnitude linear and the phase in radians
+    pub fn into_representation(mut self, representation: Representation) -> Self {
+        let (polar, complex) = match representation {
+            Representation::Polar => (true, false),
+            Representation::Complex => (false, true),
+            Representation::Both => (true, true),
+        };
+        if polar
+            && self.magnitude.is_none()
+            && let (Some(real), Some(imaginary)) = (&self.real, &self.imaginary)
+        {
+            let (magnitude, phase) = T::to_polar(real, imaginary);
+            (self.magnitude, self.phase) = (Some(magnitude), Some(phase));
+        }
+        if complex
+            && self.real.is_none()
+            && let (Some(magnitude), Some(phase)) = (&self.magnitude, &self.phase)
+        {
+            let (real, imaginary) = T::to_cartesian(magnitude, phase);
+            (self.real, self.imaginary) = (Some(real), Some(imaginary));
+        }
+        if !polar {
+            (self.magnitude, self.phase) = (None, None);
+        }
+        if !complex {
+            (self.real, self.imaginary) = (None, None);
+        }
+        self
+    }
+    /// Returns the magnitude and the phase, derived from the real and imaginary parts if missing
+    fn polar(
+        &self,
+    ) -> Option<(
+        <T as Cartesian2Polar>::Output,
+        <T as Cartesian2Polar>::Output,
+    )> {
+        match (&self.magnitude, &self.phase, &self.real, &self.imaginary) {
+            (Some(magnitude), Some(phase), ..) => Some((magnitude.clone(), phase.clone())),
+            (.., Some(real), Some(imaginary)) => Some(T::to_polar(real, imaginary)),
+            _ => None,
+        }
+    }
+    /// Returns the size of the frequency response
+    fn size(&self) -> Option<<<T as Cartesian2Polar>::Output as Dims>::D> {
+        self.magnitude
+            .as_ref()
+            .or(self.real.as_ref())
+            .map(|x| x.size())
+    }
+    /// Returns the frequency \[Hz\]
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+    /// Returns the magnitude, if any
+    pub fn magnitude(&self) -> Option<&<T as Cartesian2Polar>::Output> {
+        self.magnitude.as_ref()
+    }
+    /// Returns the phase, if any
+    pub fn phase(&self) -> Option<&<T as Cartesian2Polar>::Output> {
+        self.phase.as_ref()
+    }
+    /// Returns the real part, if any
+    pub fn real(&self) -> Option<&<T as Cartesian2Polar>::Output> {
+        self.real.as_ref()
+    }
+    /// Returns the imaginary part, if any
+    pub fn imaginary(&self) -> Option<&<T as Cartesian2Polar>::Output> {
+        self.imaginary.as_ref()
+    }
+    /// Converts the frequency from Hz to `unit`
+    pub(crate) fn into_unit(self, unit: FrequencyUnit) -> Self {
+        Self {
+            frequency: unit.hz_to(self.frequency),
+            ..self
+        }
+    }
+    /// Converts the frequency from `unit` to Hz
+    pub(crate) fn into_hz(self, unit: FrequencyUnit) -> Self {
+        Self {
+            frequency: unit.to_hz(self.frequency),
+            ..self
+        }
+    }
+    /// Returns the largest magnitude \[dB\] and phase \[rd\] errors between `m`
+    /// and the interpolation of `a` and `b`
+    ///
+    /// Returns `None` if any of the data is missing both representations
+    pub fn midpoint_error(a: &Self, m: &Self, b: &Self) -> Option<(f64, f64)> {
+        let ((a_magnitude, a_phase), (m_magnitude, m_phase), (b_magnitude, b_phase)) =
+            (a.polar()?, m.polar()?, b.polar()?);
+        Some((
+            MidpointError::magnitude_error(&a_magnitude, &m_magnitude, &b_magnitude),
+            MidpointError::phase_error(&a_phase, &m_phase, &b_phase),
+        ))
+    }
+}
+impl<T> Clone for FrequencyResponseData<T>
+where
+    T: Cartesian2Polar,
+    <T as Cartesian2Polar>::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            frequency: self.frequency,
+            magnitude: self.magnitude.clone(),
+            phase: self.phase.clone(),
+            real: self.real.clone(),
+            imaginary: self.imaginary.clone(),
+        }
+    }
+}
+impl FrequencyResponseData<DMatrix<Complex<f64>>> {
+    /// Returns the complex frequency response of the output channel `i` to the input channel `j`
+    ///
+    /// The complex value is given by the real and imaginary parts if any,
+    /// otherwise by the linear magnitude and the phase in radians
+    pub fn get(&self, i: usize, j: usize) -> Option<Complex<f64>> {
+        match (&self.real, &self.imaginary, &self.magnitude, &self.phase) {
+            (Some(re), Some(im), ..) => Some(Complex::new(*re.get((i, j))?, *im.get((i, j))?)),
+            (.., Some(m), Some(p)) => Some(Complex::from_polar(*m.get((i, j))?, *p.get((i, j))?)),
+            _ => None,
+        }
+    }
+    /// Returns the complex frequency response matrix
+    ///
+    /// The complex values are given by the real and imaginary parts if any,
+    /// otherwise by the linear magnitude and the phase in radians
+    pub fn complex_matrix(&self) -> Option<DMatrix<Complex<f64>>> {
+        match (&self.real, &self.imaginary, &self.magnitude, &self.phase) {
+            (Some(re), Some(im), ..) => Some(re.zip_map(im, Complex::new)),
+            (.., Some(m), Some(p)) => Some(m.zip_map(p, Complex::from_polar)),
+            _ => None,
+        }
+    }
+    /// Converts the magnitude and the phase from linear and radians to `magnitude_unit` and `phase_unit`
+    pub(crate) fn into_polar_units(
+        mut self,
+        magnitude_unit: MagnitudeUnit,
+        phase_unit: PhaseUnit,
+    ) -> Self {
+        if let Some(magnitude) = self.magnitude.as_mut() {
+            magnitude.apply(|x| *x = magnitude_unit.linear_to(*x));
+        }
+        if let Some(phase) = self.phase.as_mut() {
+            phase.apply(|x| *x = phase_unit.radian_to(*x));
+        }
+        self
+    }
+    /// Converts the magnitude and the phase from `magnitude_unit` and `phase_unit` to linear and radians
+    pub(crate) fn into_linear_radian(
+        mut self,
+        magnitude_unit: MagnitudeUnit,
+        phase_unit: PhaseUnit,
+    ) -> Self {
+        if let Some(magnitude) = self.magnitude.as_mut() {
+            magnitude.apply(|x| *x = magnitude_unit.to_linear(*x));
+        }
+        if let Some(phase) = self.phase.as_mut() {
+            phase.apply(|x| *x = phase_unit.to_radian(*x));
         }
+        self
     }
 }
 impl<T> Display for FrequencyResponseData<T>
@@ -102,16 +519,34 @@ where
     <T as Cartesian2Polar>::Output: Display,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{},{},{}", self.frequency, self.magnitude, self.phase)
+        write!(f, "{}", self.frequency)?;
+        for x in [&self.magnitude, &self.phase, &self.real, &self.imaginary]
+            .into_iter()
+            .flatten()
+        {
+            write!(f, ",{x}")?;
+        }
+        Ok(())
     }
 }
 
 /// Collection of [FrequencyResponseData]
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(bound(deserialize = "<T as Cartesian2Polar>::Output: Deserialize<'de>"))]
 pub struct FrequencyResponseVec<T: Cartesian2Polar>(
     #[serde(rename = "data")] Vec<FrequencyResponseData<T>>,
 );
 
+impl<T> Clone for FrequencyResponseVec<T>
+where
+    T: Cartesian2Polar,
+    <T as Cartesian2Polar>::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
 impl<T: Cartesian2Polar> FrequencyResponseVec<T> {
     /// Creates a new [FrequencyResponseVec] instance from a vector of [FrequencyResponseData]
     pub fn new(frequency_response_datas: Vec<FrequencyResponseData<T>>) -> Self {
@@ -138,12 +573,13 @@ impl<T: Cartesian2Polar> FromIterator<FrequencyResponseData<T>> for FrequencyRes
 
 impl<T: Cartesian2Polar> Display for FrequencyResponseVec<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "GMT FEM frequency response matrix {}x{:?}",
-            self.len(),
-            self[0].magnitude.size()
-        )?;
+        let Some(first) = self.first() else {
+            return writeln!(f, "GMT FEM frequency response: empty");
+        };
+        write!(f, "GMT FEM frequency response matrix {}", self.len())?;
+        if let Some(size) = first.size() {
+            write!(f, "x{size:?}")?;
+        }
         match self.len() {
             n if n == 1 => {
                 writeln!(f, " @ {:.2}Hz", self[0].frequency)
@@ -163,55 +599,679 @@ impl<T: Cartesian2Polar> Display for FrequencyResponseVec<T> {
     }
 }
 
+/// Frequency response layout in the data files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// a list of frequency response data, one per frequency
+    #[default]
+    Records,
+    /// a frequency vector and `ny×nu×nf` frequency response arrays
+    Columnar,
+}
+impl Layout {
+    /// Returns the name of the layout
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Records => "records",
+            Self::Columnar => "columnar",
+        }
+    }
+}
+
+/// Frequency response in the columnar layout
+///
+/// The frequency response components are `ny×nu×nf` arrays,
+/// written as nested lists `[ny][nu][nf]` to the pickle files and as 3-D arrays to the Matlab files
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnarFrequencyResponse {
+    pub frequency: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magnitude: Option<Vec<Vec<Vec<f64>>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase: Option<Vec<Vec<Vec<f64>>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub real: Option<Vec<Vec<Vec<f64>>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imaginary: Option<Vec<Vec<Vec<f64>>>>,
+}
+impl ColumnarFrequencyResponse {
+    /// Returns the name, the dimensions and the column-major data of the frequency response arrays
+    fn arrays(&self) -> Vec<(&'static str, Vec<u64>, Vec<f64>)> {
+        [
+            ("magnitude", &self.magnitude),
+            ("phase", &self.phase),
+            ("real", &self.real),
+            ("imaginary", &self.imaginary),
+        ]
+        .into_iter()
+        .filter_map(|(name, array)| {
+            let array = array.as_ref()?;
+            let (ny, nu, nf) = (array.len(), array.first()?.len(), self.frequency.len());
+            let mut data = Vec::with_capacity(ny * nu * nf);
+            for k in 0..nf {
+                for j in 0..nu {
+                    data.extend(array.iter().map(|row| row[j][k]));
+                }
+            }
+            Some((name, vec![ny as u64, nu as u64, nf as u64], data))
+        })
+        .collect()
+    }
+}
+impl From<&FrequencyResponseVec<DMatrix<Complex<f64>>>> for ColumnarFrequencyResponse {
+    fn from(records: &FrequencyResponseVec<DMatrix<Complex<f64>>>) -> Self {
+        let array = |component: fn(
+            &FrequencyResponseData<DMatrix<Complex<f64>>>,
+        ) -> Option<&DMatrix<f64>>| {
+            let matrices = records.iter().map(component).collect::<Option<Vec<_>>>()?;
+            let (ny, nu) = matrices.first()?.shape();
+            Some(
+                (0..ny)
+                    .map(|i| {
+                        (0..nu)
+                            .map(|j| matrices.iter().map(|m| m[(i, j)]).collect())
+                            .collect()
+                    })
+                    .collect(),
+            )
+        };
+        Self {
+            frequency: records.frequencies(),
+            magnitude: array(|data| data.magnitude.as_ref()),
+            phase: array(|data| data.phase.as_ref()),
+            real: array(|data| data.real.as_ref()),
+            imaginary: array(|data| data.imaginary.as_ref()),
+        }
+    }
+}
+impl From<ColumnarFrequencyResponse> for FrequencyResponseVec<DMatrix<Complex<f64>>> {
+    fn from(columnar: ColumnarFrequencyResponse) -> Self {
+        let matrix = |array: &Option<Vec<Vec<Vec<f64>>>>, k: usize| {
+            array.as_ref().map(|array| {
+                let nu = array.first().map_or(0, Vec::len);
+                DMatrix::from_fn(array.len(), nu, |i, j| array[i][j][k])
+            })
+        };
+        columnar
+            .frequency
+            .iter()
+            .enumerate()
+            .map(|(k, &frequency)| FrequencyResponseData {
+                frequency,
+                magnitude: matrix(&columnar.magnitude, k),
+                phase: matrix(&columnar.phase, k),
+                real: matrix(&columnar.real, k),
+                imaginary: matrix(&columnar.imaginary, k),
+            })
+            .collect()
+    }
+}
+
+/// Frequency response in either the records or the columnar [Layout]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FrequencyResponseLayout {
+    Records(FrequencyResponseVec<DMatrix<Complex<f64>>>),
+    Columnar(ColumnarFrequencyResponse),
+}
+impl Default for FrequencyResponseLayout {
+    fn default() -> Self {
+        Self::Records(Default::default())
+    }
+}
+impl FrequencyResponseLayout {
+    /// Returns the frequency response in the records layout
+    pub fn into_records(self) -> FrequencyResponseVec<DMatrix<Complex<f64>>> {
+        match self {
+            Self::Records(records) => records,
+            Self::Columnar(columnar) => columnar.into(),
+        }
+    }
+    /// Returns the size `(ny,nu)` of the frequency response matrices
+    fn size(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Records(records) => records.first()?.size(),
+            Self::Columnar(columnar) => [
+                &columnar.magnitude,
+                &columnar.phase,
+                &columnar.real,
+                &columnar.imaginary,
+            ]
+            .into_iter()
+            .flatten()
+            .next()
+            .map(|array| (array.len(), array.first().map_or(0, Vec::len))),
+        }
+    }
+    /// Converts the frequency response to the records layout and returns the records
+    fn records_mut(&mut self) -> &mut Vec<FrequencyResponseData<DMatrix<Complex<f64>>>> {
+        if let Self::Columnar(columnar) = self {
+            *self = Self::Records(std::mem::take(columnar).into());
+        }
+        match self {
+            Self::Records(records) => &mut records.0,
+            Self::Columnar(_) => {
+                unreachable!("the frequency response has been converted to records")
+            }
+        }
+    }
+}
+
 /// GMT FEM transfer function data export
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TransferFunctionData {
     fem: String,
     inputs: Vec<String>,
     outputs: Vec<String>,
-    modal_damping_coefficient: f64,
+    #[serde(default)]
+    modal_damping_model: DampingModel,
+    /// the modal damping coefficient of the data files written before the damping schedules
+    /// is read as a constant schedule
+    #[serde(
+        alias = "modal_damping_coefficient",
+        deserialize_with = "deserialize_modal_damping"
+    )]
+    modal_damping: DampingSchedule,
     fem_eigen_frequency_range: (f64, f64),
-    frequency_response: FrequencyResponseVec<DMatrix<Complex<f64>>>,
+    /// FEM inputs in the order of the frequency response columns
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fem_inputs: Vec<FemIo>,
+    /// FEM outputs in the order of the frequency response rows
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fem_outputs: Vec<FemIo>,
+    /// fingerprint of the structural model the frequency response is computed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    structural: Option<StructuralCacheKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shard: Option<Shard>,
+    /// units of the frequencies written to the data files
+    #[serde(default)]
+    pub(crate) frequency_unit: FrequencyUnit,
+    /// representation of the frequency response written to the data files
+    #[serde(default)]
+    pub(crate) representation: Representation,
+    /// units of the magnitude written to the data files
+    #[serde(default)]
+    pub(crate) magnitude_unit: MagnitudeUnit,
+    /// units of the phase written to the data files
+    #[serde(default)]
+    pub(crate) phase_unit: PhaseUnit,
+    /// phase unwrapped along the sorted frequencies in the data files
+    #[serde(default)]
+    pub(crate) unwrapped_phase: bool,
+    /// layout of the frequency response in the data files
+    #[serde(default)]
+    pub(crate) layout: Layout,
+    frequency_response: FrequencyResponseLayout,
+}
+
+/// Deserializes either a [DampingSchedule] or a damping coefficient as a constant schedule
+fn deserialize_modal_damping<'de, D>(
+    deserializer: D,
+) -> std::result::Result<DampingSchedule, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ModalDamping {
+        Coefficient(f64),
+        Schedule(DampingSchedule),
+    }
+    Ok(match ModalDamping::deserialize(deserializer)? {
+        ModalDamping::Coefficient(z) => DampingSchedule::Constant(z),
+        ModalDamping::Schedule(schedule) => schedule,
+    })
 }
 
 impl From<&Cli> for TransferFunctionData {
     fn from(args: &Cli) -> Self {
-        let fem = {
-            let fem_repo = env::var("FEM_REPO").unwrap();
-            let fem_path = Path::new(&fem_repo);
-            fem_path.file_name().unwrap().to_string_lossy().into_owned()
-        };
-        let inputs: Vec<_> = args.inputs.iter().map(|x| x.name()).collect();
-        let outputs: Vec<_> = args.outputs.iter().map(|x| x.name()).collect();
         Self {
-            fem,
-            inputs,
-            outputs,
-            modal_damping_coefficient: args.structural_damping,
+            inputs: args.inputs.iter().map(|io| io.to_string()).collect(),
+            outputs: args.outputs.iter().map(|io| io.to_string()).collect(),
+            frequency_unit: args.export_frequency_unit,
+            representation: args.representation,
+            magnitude_unit: args.magnitude_unit,
+            phase_unit: args.phase_unit,
+            unwrapped_phase: args.unwrap_phase,
+            layout: args.layout,
             ..Default::default()
         }
     }
 }
 
+/// Conversion of the frequency responses to and from the data files conventions
+///
+/// The phase is unwrapped along the successive frequency responses given to [Exporter::export]
+#[derive(Debug, Clone)]
+pub(crate) struct Exporter {
+    frequency_unit: FrequencyUnit,
+    representation: Representation,
+    magnitude_unit: MagnitudeUnit,
+    phase_unit: PhaseUnit,
+    unwrapped_phase: bool,
+    // unwrapped phase of the previous frequency response [rd]
+    previous_phase: Option<DMatrix<f64>>,
+}
+impl Exporter {
+    /// Creates an exporter with the conventions of the transfer functions data
+    pub(crate) fn new(data: &TransferFunctionData) -> Self {
+        Self {
+            frequency_unit: data.frequency_unit,
+            representation: data.representation,
+            magnitude_unit: data.magnitude_unit,
+            phase_unit: data.phase_unit,
+            unwrapped_phase: data.unwrapped_phase,
+            previous_phase: None,
+        }
+    }
+    /// Returns true if the phase is unwrapped
+    pub(crate) fn unwraps_phase(&self) -> bool {
+        self.unwrapped_phase
+    }
+    /// Converts a frequency response to the data files conventions
+    pub(crate) fn export(
+        &mut self,
+        data: FrequencyResponseData<DMatrix<Complex<f64>>>,
+    ) -> FrequencyResponseData<DMatrix<Complex<f64>>> {
+        let mut data = data.into_representation(self.representation);
+        if self.unwrapped_phase
+            && let Some(phase) = data.phase.as_mut()
+        {
+            if let Some(previous) = &self.previous_phase {
+                phase.zip_apply(previous, |p, q| *p = q + wrap_phase(*p - q));
+            }
+            self.previous_phase = Some(phase.clone());
+        }
+        data.into_unit(self.frequency_unit)
+            .into_polar_units(self.magnitude_unit, self.phase_unit)
+    }
+    /// Converts a frequency response from the data files conventions
+    ///
+    /// The unwrapped phase is not wrapped back
+    pub(crate) fn import(
+        &self,
+        data: FrequencyResponseData<DMatrix<Complex<f64>>>,
+    ) -> FrequencyResponseData<DMatrix<Complex<f64>>> {
+        data.into_hz(self.frequency_unit)
+            .into_linear_radian(self.magnitude_unit, self.phase_unit)
+    }
+}
+
+/// Converts a [DampingSchedule] into a Matlab structure
+fn damping_to_mat<'a>(
+    name: &str,
+    damping: &DampingSchedule,
+) -> std::result::Result<matio_rs::Mat<'a>, matio_rs::MatioError> {
+    use matio_rs::{Mat, MayBeFrom};
+    let fields = match damping {
+        DampingSchedule::Constant(z) => vec![
+            Mat::maybe_from("schedule", "constant")?,
+            Mat::maybe_from("z", *z)?,
+        ],
+        DampingSchedule::Bands { default, bands } => vec![
+            Mat::maybe_from("schedule", "bands")?,
+            Mat::maybe_from("default", *default)?,
+            Mat::maybe_from("lower", bands.iter().map(|b| b.lower).collect::<Vec<_>>())?,
+            Mat::maybe_from("upper", bands.iter().map(|b| b.upper).collect::<Vec<_>>())?,
+            Mat::maybe_from("z", bands.iter().map(|b| b.z).collect::<Vec<_>>())?,
+        ],
+        DampingSchedule::Modal(z) => vec![
+            Mat::maybe_from("schedule", "modal")?,
+            Mat::maybe_from("z", z)?,
+        ],
+        DampingSchedule::Rayleigh { alpha, beta } => vec![
+            Mat::maybe_from("schedule", "rayleigh")?,
+            Mat::maybe_from("alpha", *alpha)?,
+            Mat::maybe_from("beta", *beta)?,
+        ],
+    };
+    Mat::maybe_from(name, fields)
+}
+
+/// Top level Matlab variable with the strings of the `transfer_functions` structure
+///
+/// matio-rs reads back the character arrays of the top level variables only,
+/// so the strings of the structure are also written to this variable as a JSON object.
+/// The Matlab files without this variable are read with [MatStrings::infer]
+const MAT_STRINGS: &str = "transfer_functions_strings";
+
+/// Strings of the `transfer_functions` Matlab structure
+#[derive(Debug, Serialize, Deserialize)]
+struct MatStrings {
+    fem: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    modal_damping_model: String,
+    modal_damping_schedule: String,
+    frequency_unit: String,
+    representation: String,
+    magnitude_unit: String,
+    phase_unit: String,
+    layout: String,
+    fem_inputs: Vec<String>,
+    fem_outputs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    structural: Option<StructuralCacheKey>,
+    shard: Option<String>,
+}
+impl From<&TransferFunctionData> for MatStrings {
+    fn from(data: &TransferFunctionData) -> Self {
+        Self {
+            fem: data.fem.clone(),
+            inputs: data.inputs.clone(),
+            outputs: data.outputs.clone(),
+            modal_damping_model: data.modal_damping_model.name().to_string(),
+            modal_damping_schedule: match data.modal_damping {
+                DampingSchedule::Constant(_) => "constant",
+                DampingSchedule::Bands { .. } => "bands",
+                DampingSchedule::Modal(_) => "modal",
+                DampingSchedule::Rayleigh { .. } => "rayleigh",
+            }
+            .to_string(),
+            frequency_unit: data.frequency_unit.name().to_string(),
+            representation: data.representation.name().to_string(),
+            magnitude_unit: data.magnitude_unit.name().to_string(),
+            phase_unit: data.phase_unit.name().to_string(),
+            layout: data.layout.name().to_string(),
+            fem_inputs: data.fem_inputs.iter().map(|io| io.name.clone()).collect(),
+            fem_outputs: data.fem_outputs.iter().map(|io| io.name.clone()).collect(),
+            structural: data.structural.clone(),
+            shard: data.shard.as_ref().map(|shard| shard.to_string()),
+        }
+    }
+}
+impl MatStrings {
+    /// Infers the strings of a `transfer_functions` Matlab structure written without [MAT_STRINGS],
+    /// e.g. by an earlier version or saved back from Matlab
+    ///
+    /// The names cannot be read back and are left empty, the modal damping schedule,
+    /// the representation and the layout are inferred from the structure fields
+    /// and the other conventions are the defaults
+    fn infer(tf: &matio_rs::Mat) -> Result<Self> {
+        let response = mat_field(tf, "frequency_response")?;
+        let (layout, data) = match mat_optional_field(response, "data")? {
+            Some(data) => (Layout::Records, data),
+            None => (Layout::Columnar, response),
+        };
+        let representation = match (
+            mat_optional_field(data, "magnitude")?.is_some(),
+            mat_optional_field(data, "real")?.is_some(),
+        ) {
+            (true, true) => Representation::Both,
+            (false, true) => Representation::Complex,
+            _ => Representation::Polar,
+        };
+        let modal_damping_schedule = match mat_optional_field(tf, "modal_damping")? {
+            Some(damping) if mat_optional_field(damping, "default")?.is_some() => "bands",
+            Some(damping) if mat_optional_field(damping, "alpha")?.is_some() => "rayleigh",
+            Some(damping) if mat_field(damping, "z")?.len() > 1 => "modal",
+            _ => "constant",
+        };
+        Ok(Self {
+            fem: String::new(),
+            inputs: vec![],
+            outputs: vec![],
+            modal_damping_model: DampingModel::default().name().to_string(),
+            modal_damping_schedule: modal_damping_schedule.to_string(),
+            frequency_unit: FrequencyUnit::default().name().to_string(),
+            representation: representation.name().to_string(),
+            magnitude_unit: MagnitudeUnit::default().name().to_string(),
+            phase_unit: PhaseUnit::default().name().to_string(),
+            layout: layout.name().to_string(),
+            fem_inputs: vec![],
+            fem_outputs: vec![],
+            structural: None,
+            shard: None,
+        })
+    }
+}
+
+/// Writes the array `name` with the given `shape` and `order` to a NumPy archive
+fn npz_array<W, T>(
+    npz: &mut npyz::npz::NpzWriter<W>,
+    name: &str,
+    shape: &[u64],
+    order: npyz::Order,
+    data: impl IntoIterator<Item = T>,
+) -> io::Result<()>
+where
+    W: io::Write + io::Seek,
+    T: npyz::AutoSerialize,
+{
+    use npyz::WriterBuilder;
+    let mut writer = npz
+        .array::<T>(name, Default::default())?
+        .default_dtype()
+        .shape(shape)
+        .order(order)
+        .begin_nd()?;
+    writer.extend(data)?;
+    writer.finish()
+}
+
+/// Writes the unicode strings array `name` with the given `shape` to a NumPy archive
+fn npz_strings<W>(
+    npz: &mut npyz::npz::NpzWriter<W>,
+    name: &str,
+    shape: &[u64],
+    data: &[&str],
+) -> io::Result<()>
+where
+    W: io::Write + io::Seek,
+{
+    use npyz::WriterBuilder;
+    let width = data.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+    let dtype = npyz::DType::Plain(
+        format!("<U{}", width.max(1))
+            .parse()
+            .map_err(io::Error::other)?,
+    );
+    let mut writer = npz
+        .array::<str>(name, Default::default())?
+        .dtype(dtype)
+        .shape(shape)
+        .begin_nd()?;
+    for x in data {
+        writer.push(x)?;
+    }
+    writer.finish()
+}
+
+/// Writes a [DampingSchedule] to a NumPy archive as the arrays `{name}_schedule`
+/// and `{name}_{parameter}` for each parameter of the schedule
+fn damping_to_npz<W>(
+    npz: &mut npyz::npz::NpzWriter<W>,
+    name: &str,
+    damping: &DampingSchedule,
+) -> io::Result<()>
+where
+    W: io::Write + io::Seek,
+{
+    use npyz::Order;
+    let (schedule, parameters): (_, Vec<(&str, Vec<f64>)>) = match damping {
+        DampingSchedule::Constant(z) => ("constant", vec![("z", vec![*z])]),
+        DampingSchedule::Bands { default, bands } => (
+            "bands",
+            vec![
+                ("default", vec![*default]),
+                ("lower", bands.iter().map(|b| b.lower).collect()),
+                ("upper", bands.iter().map(|b| b.upper).collect()),
+                ("z", bands.iter().map(|b| b.z).collect()),
+            ],
+        ),
+        DampingSchedule::Modal(z) => ("modal", vec![("z", z.clone())]),
+        DampingSchedule::Rayleigh { alpha, beta } => (
+            "rayleigh",
+            vec![("alpha", vec![*alpha]), ("beta", vec![*beta])],
+        ),
+    };
+    npz_strings(npz, &format!("{name}_schedule"), &[], &[schedule])?;
+    for (parameter, values) in parameters {
+        npz_array(
+            npz,
+            &format!("{name}_{parameter}"),
+            &[values.len() as u64],
+            Order::C,
+            values,
+        )?;
+    }
+    Ok(())
+}
+
+/// Converts a Matlab structure written with [damping_to_mat] into a [DampingSchedule]
+///
+/// The `schedule` name is read from [MatStrings] as matio-rs does not read the structures strings
+fn damping_from_mat(schedule: String, damping: &matio_rs::Mat) -> Result<DampingSchedule> {
+    use matio_rs::MayBeInto;
+    Ok(match schedule.as_str() {
+        "constant" => DampingSchedule::Constant(mat_field(damping, "z")?.maybe_into()?),
+        "bands" => {
+            let lower: Vec<f64> = mat_field(damping, "lower")?.maybe_into()?;
+            let upper: Vec<f64> = mat_field(damping, "upper")?.maybe_into()?;
+            let z: Vec<f64> = mat_field(damping, "z")?.maybe_into()?;
+            DampingSchedule::Bands {
+                default: mat_field(damping, "default")?.maybe_into()?,
+                bands: lower
+                    .into_iter()
+                    .zip(upper)
+                    .zip(z)
+                    .map(|((lower, upper), z)| DampingBand { lower, upper, z })
+                    .collect(),
+            }
+        }
+        "modal" => DampingSchedule::Modal(mat_field(damping, "z")?.maybe_into()?),
+        "rayleigh" => DampingSchedule::Rayleigh {
+            alpha: mat_field(damping, "alpha")?.maybe_into()?,
+            beta: mat_field(damping, "beta")?.maybe_into()?,
+        },
+        _ => {
+            return Err(TransferFunctionDataError::MatlabValue(
+                "modal damping schedule",
+                schedule,
+            ));
+        }
+    })
+}
+
+/// Returns the field `name` of a Matlab structure
+fn mat_field<'a>(mat: &'a matio_rs::Mat<'_>, name: &str) -> Result<&'a matio_rs::Mat<'a>> {
+    Ok(mat.field(name)?[0])
+}
+
+/// Returns the field `name` of a Matlab structure, if any
+fn mat_optional_field<'a>(
+    mat: &'a matio_rs::Mat<'_>,
+    name: &str,
+) -> Result<Option<&'a matio_rs::Mat<'a>>> {
+    match mat.field(name) {
+        Ok(field) => Ok(Some(field[0])),
+        Err(matio_rs::MatioError::FieldNotFound(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns the matrix `name` of a Matlab structure, if any
+fn mat_matrix(mat: &matio_rs::Mat, name: &str) -> Result<Option<DMatrix<f64>>> {
+    use matio_rs::MayBeInto;
+    Ok(mat_optional_field(mat, name)?
+        .map(|matrix| matrix.maybe_into())
+        .transpose()?)
+}
+
+/// Returns the `ny×nu×nf` array `name` of a Matlab structure, if any,
+/// as nested lists `[ny][nu][nf]`
+fn mat_array(mat: &matio_rs::Mat, name: &str) -> Result<Option<Vec<Vec<Vec<f64>>>>> {
+    use matio_rs::MayBeInto;
+    let Some(array) = mat_optional_field(mat, name)? else {
+        return Ok(None);
+    };
+    let dims = array.dims();
+    let (ny, nu) = (dims[0], dims.get(1).copied().unwrap_or(1));
+    let data: Vec<f64> = array.maybe_into()?;
+    let nf = data.len() / (ny * nu).max(1);
+    Ok(Some(
+        (0..ny)
+            .map(|i| {
+                (0..nu)
+                    .map(|j| (0..nf).map(|k| data[i + ny * (j + nu * k)]).collect())
+                    .collect()
+            })
+            .collect(),
+    ))
+}
+
+/// Returns the name and the channel index of the `n` channels of the FEM inputs or outputs
+///
+/// The channel index is the FEM channel index of the channels selection of the input or output, if any,
+/// the channels are named `default` with their index if the FEM inputs or outputs do not match `n`
+fn io_channels(fem_io: &[FemIo], n: usize, default: &str) -> Vec<(String, usize)> {
+    if fem_io.is_empty() || fem_io.iter().map(|io| io.size).sum::<usize>() != n {
+        return (0..n).map(|k| (default.to_string(), k)).collect();
+    }
+    fem_io
+        .iter()
+        .flat_map(|io| (0..io.size).map(move |k| (io.name.clone(), io.channel(k))))
+        .collect()
+}
+
+/// Returns the FEM channel index of each channel of the FEM inputs or outputs
+fn fem_io_channels(fem_io: &[FemIo]) -> impl Iterator<Item = u64> + '_ {
+    fem_io
+        .iter()
+        .flat_map(|io| (0..io.size).map(move |k| io.channel(k) as u64))
+}
+
+/// Returns the variant of `T` with the given `name`
+fn parse_name<T: clap::ValueEnum>(
+    what: &'static str,
+    name: String,
+    to_name: fn(&T) -> &str,
+) -> Result<T> {
+    T::value_variants()
+        .iter()
+        .find(|variant| to_name(variant) == name)
+        .cloned()
+        .ok_or(TransferFunctionDataError::MatlabValue(what, name))
+}
+
 impl TransferFunctionData {
-    /// Writes the date to either a pickle or matlab file
+    /// Writes the date to either a pickle, matlab, NumPy, Universal File Format,
+    /// Apache Parquet or Arrow IPC file
     ///
-    /// The file extension, "pkl" or "mat", sets the file type
+    /// The file extension, "pkl", "mat", "npz", "uff" or "unv", "parquet", "arrow" or "feather",
+    /// sets the file type, the Parquet and Arrow IPC files require the `tabular` feature
     pub fn dump(self, path: impl AsRef<Path>) -> Result<()> {
+        const EXTENSIONS: &str =
+            r#""arrow", "feather", "mat", "npz", "parquet", "pkl", "uff" or "unv""#;
         let now = Instant::now();
         match path.as_ref().extension() {
             Some(ext) if ext == "pkl" => {
                 let file = File::create(&path)?;
                 let mut buffer = BufWriter::new(file);
-                serde_pickle::to_writer(&mut buffer, &self, Default::default())?;
+                serde_pickle::to_writer(&mut buffer, &self.export(), Default::default())?;
             }
             Some(ext) if ext == "mat" => self.dump_to_mat(&path)?,
+            Some(ext) if ext == "npz" => self.dump_to_npz(&path)?,
+            Some(ext) if ext == "uff" || ext == "unv" => self.dump_to_uff(&path)?,
+            #[cfg(feature = "tabular")]
+            Some(ext) if ext == "parquet" => self.dump_to_parquet(&path)?,
+            #[cfg(feature = "tabular")]
+            Some(ext) if ext == "arrow" || ext == "feather" => self.dump_to_arrow(&path)?,
+            #[cfg(not(feature = "tabular"))]
+            Some(ext) if ext == "parquet" || ext == "arrow" || ext == "feather" => {
+                return Err(TransferFunctionDataError::TabularFeature(
+                    ext.to_string_lossy().into_owned(),
+                ));
+            }
             Some(ext) => {
                 return Err(TransferFunctionDataError::DataFileExtension(
                     ext.to_string_lossy().into_owned(),
+                    EXTENSIONS,
                 ));
             }
-            None => return Err(TransferFunctionDataError::MissingFileExtension),
+            None => return Err(TransferFunctionDataError::MissingFileExtension(EXTENSIONS)),
         };
         println!(
             "Frequency response written to {} in {}ms",
@@ -221,48 +1281,1173 @@ impl TransferFunctionData {
         Ok(())
     }
 
+    /// Writes the data to a Matlab file
+    ///
+    /// The data is written to the structure `transfer_functions` and its strings
+    /// are also written as a JSON object to the variable `transfer_functions_strings`
     pub fn dump_to_mat(self, path: impl AsRef<Path>) -> Result<()> {
-        use matio_rs::{Mat, MatFile, MayBeFrom};
+        use matio_rs::{Mat, MatArray, MatFile, MayBeFrom};
+        let this = self.export();
+        let arrays = match &this.frequency_response {
+            FrequencyResponseLayout::Columnar(columnar) => columnar.arrays(),
+            FrequencyResponseLayout::Records(_) => vec![],
+        };
+        let strings = serde_json::to_string(&MatStrings::from(&this))?;
         let mut fields = vec![
-            Mat::maybe_from("fem", self.fem)?,
-            Mat::maybe_from("inputs", self.inputs)?,
-            Mat::maybe_from("outputs", self.outputs)?,
-            Mat::maybe_from("modal_damping_coefficient", self.modal_damping_coefficient)?,
-            Mat::maybe_from("fem_eigen_frequency_range", self.fem_eigen_frequency_range)?,
+            Mat::maybe_from("fem", this.fem)?,
+            Mat::maybe_from("inputs", this.inputs)?,
+            Mat::maybe_from("outputs", this.outputs)?,
+            Mat::maybe_from("modal_damping_model", this.modal_damping_model.name())?,
+            damping_to_mat("modal_damping", &this.modal_damping)?,
+            Mat::maybe_from("fem_eigen_frequency_range", this.fem_eigen_frequency_range)?,
+            Mat::maybe_from("frequency_unit", this.frequency_unit.name())?,
+            Mat::maybe_from("representation", this.representation.name())?,
+            Mat::maybe_from("magnitude_unit", this.magnitude_unit.name())?,
+            Mat::maybe_from("phase_unit", this.phase_unit.name())?,
+            Mat::maybe_from("unwrapped_phase", this.unwrapped_phase as u8)?,
+            Mat::maybe_from("layout", this.layout.name())?,
         ];
-        let mut data = vec![];
-        for r in self.frequency_response.iter() {
-            let data_fields = vec![
-                Mat::maybe_from("frequency", r.frequency)?,
-                Mat::maybe_from("magnitude", r.magnitude.clone())?,
-                Mat::maybe_from("phase", r.phase.clone())?,
-            ];
-            data.push(Mat::maybe_from("data", data_fields)?);
+        for (name, io) in [
+            ("fem_inputs", &this.fem_inputs),
+            ("fem_outputs", &this.fem_outputs),
+        ] {
+            if !io.is_empty() {
+                let (names, sizes): (Vec<_>, Vec<_>) = io
+                    .iter()
+                    .map(|io| (io.name.clone(), io.size as u64))
+                    .unzip();
+                fields.push(Mat::maybe_from(name, names)?);
+                fields.push(Mat::maybe_from(format!("{name}_size"), sizes)?);
+                fields.push(Mat::maybe_from(
+                    format!("{name}_channels"),
+                    fem_io_channels(io).collect::<Vec<_>>(),
+                )?);
+            }
+        }
+        if let Some(shard) = this.shard {
+            fields.push(Mat::maybe_from("shard", shard.to_string())?);
+        }
+        match &this.frequency_response {
+            FrequencyResponseLayout::Records(records) => {
+                let mut data = vec![];
+                for r in records.iter() {
+                    let mut data_fields = vec![Mat::maybe_from("frequency", r.frequency)?];
+                    for (name, x) in [
+                        ("magnitude", &r.magnitude),
+                        ("phase", &r.phase),
+                        ("real", &r.real),
+                        ("imaginary", &r.imaginary),
+                    ] {
+                        if let Some(x) = x {
+                            data_fields.push(Mat::maybe_from(name, x.clone())?);
+                        }
+                    }
+                    data.push(Mat::maybe_from("data", data_fields)?);
+                }
+                let data_iter = Box::new(data.into_iter()) as Box<dyn Iterator<Item = Mat>>;
+                fields.push(Mat::maybe_from("frequency_response", vec![data_iter])?);
+            }
+            FrequencyResponseLayout::Columnar(columnar) => {
+                let mut data_fields = vec![Mat::maybe_from("frequency", &columnar.frequency)?];
+                for (name, dims, data) in &arrays {
+                    data_fields.push(Mat::maybe_from(
+                        *name,
+                        MatArray::new(data.as_slice(), dims.clone()),
+                    )?);
+                }
+                fields.push(Mat::maybe_from("frequency_response", data_fields)?);
+            }
         }
-        let data_iter = Box::new(data.into_iter()) as Box<dyn Iterator<Item = Mat>>;
-        fields.push(Mat::maybe_from("frequency_response", vec![data_iter])?);
         let mstruct = Mat::maybe_from("transfer_functions", fields)?;
-        MatFile::save(path)?.write(mstruct);
+        MatFile::save(path)?
+            .write(mstruct)
+            .write(Mat::maybe_from(MAT_STRINGS, strings)?);
+        Ok(())
+    }
+
+    /// Writes the data to a NumPy archive
+    ///
+    /// The frequency response components are written as `ny×nu×nf` arrays whatever the layout,
+    /// the strings as unicode arrays and the boolean as unsigned integer
+    pub fn dump_to_npz(self, path: impl AsRef<Path>) -> Result<()> {
+        use npyz::{Order, npz::NpzWriter};
+        let this = self.export();
+        let columnar = match &this.frequency_response {
+            FrequencyResponseLayout::Columnar(columnar) => Cow::Borrowed(columnar),
+            FrequencyResponseLayout::Records(records) => Cow::Owned(records.into()),
+        };
+        let mut npz = NpzWriter::create(path)?;
+        npz_strings(&mut npz, "fem", &[], &[&this.fem])?;
+        for (name, io) in [("inputs", &this.inputs), ("outputs", &this.outputs)] {
+            let io: Vec<_> = io.iter().map(String::as_str).collect();
+            npz_strings(&mut npz, name, &[io.len() as u64], &io)?;
+        }
+        npz_strings(
+            &mut npz,
+            "modal_damping_model",
+            &[],
+            &[this.modal_damping_model.name()],
+        )?;
+        damping_to_npz(&mut npz, "modal_damping", &this.modal_damping)?;
+        let (eigen_min, eigen_max) = this.fem_eigen_frequency_range;
+        npz_array(
+            &mut npz,
+            "fem_eigen_frequency_range",
+            &[2],
+            Order::C,
+            [eigen_min, eigen_max],
+        )?;
+        for (name, io) in [
+            ("fem_inputs", &this.fem_inputs),
+            ("fem_outputs", &this.fem_outputs),
+        ] {
+            if !io.is_empty() {
+                let names: Vec<_> = io.iter().map(|io| io.name.as_str()).collect();
+                npz_strings(&mut npz, name, &[io.len() as u64], &names)?;
+                npz_array(
+                    &mut npz,
+                    &format!("{name}_size"),
+                    &[io.len() as u64],
+                    Order::C,
+                    io.iter().map(|io| io.size as u64),
+                )?;
+                let channels: Vec<_> = fem_io_channels(io).collect();
+                npz_array(
+                    &mut npz,
+                    &format!("{name}_channels"),
+                    &[channels.len() as u64],
+                    Order::C,
+                    channels,
+                )?;
+            }
+        }
+        if let Some(shard) = this.shard {
+            npz_strings(&mut npz, "shard", &[], &[&shard.to_string()])?;
+        }
+        for (name, value) in [
+            ("frequency_unit", this.frequency_unit.name()),
+            ("representation", this.representation.name()),
+            ("magnitude_unit", this.magnitude_unit.name()),
+            ("phase_unit", this.phase_unit.name()),
+        ] {
+            npz_strings(&mut npz, name, &[], &[value])?;
+        }
+        npz_array(
+            &mut npz,
+            "unwrapped_phase",
+            &[],
+            Order::C,
+            [this.unwrapped_phase as u8],
+        )?;
+        npz_array(
+            &mut npz,
+            "frequency",
+            &[columnar.frequency.len() as u64],
+            Order::C,
+            columnar.frequency.iter().copied(),
+        )?;
+        for (name, dims, data) in columnar.arrays() {
+            npz_array(&mut npz, name, &dims, Order::Fortran, data)?;
+        }
+        npz.zip_writer().finish().map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    /// Writes the data to a Universal File Format file
+    ///
+    /// Each output and input channels pair is written as a dataset 58 with the complex frequency response
+    /// at the sorted frequencies in Hz,
+    /// whatever the frequency unit, the representation and the polar units of the data
+    pub fn dump_to_uff(self, path: impl AsRef<Path>) -> Result<()> {
+        let (outputs, inputs) = self.channels();
+        let mut records = self.frequency_response().into_owned();
+        records.sort_by(|a, b| a.frequency().total_cmp(&b.frequency()));
+        let matrices = records
+            .iter()
+            .map(|data| {
+                data.complex_matrix()
+                    .ok_or(TransferFunctionDataError::Representation(data.frequency()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let frequencies: Vec<_> = records.iter().map(|data| data.frequency()).collect();
+        let (output_nodes, input_nodes) = (uff::node_numbers(&outputs), uff::node_numbers(&inputs));
+        let mut buffer = BufWriter::new(File::create(path)?);
+        let mut id = 0;
+        for (j, ((input, input_channel), input_node)) in inputs.iter().zip(&input_nodes).enumerate()
+        {
+            for (i, ((output, output_channel), output_node)) in
+                outputs.iter().zip(&output_nodes).enumerate()
+            {
+                id += 1;
+                let frf = FrequencyResponseFunction {
+                    id,
+                    fem: &self.fem,
+                    response: (output, *output_channel, *output_node),
+                    reference: (input, *input_channel, *input_node),
+                    frequencies: &frequencies,
+                    values: matrices.iter().map(|m| m[(i, j)]).collect(),
+                };
+                write!(buffer, "{frf}")?;
+            }
+        }
+        buffer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the data to an Apache Parquet file in the long format
+    ///
+    /// See [TransferFunctionData::dump_to_arrow]
+    #[cfg(feature = "tabular")]
+    pub fn dump_to_parquet(self, path: impl AsRef<Path>) -> Result<()> {
+        self.dump_to_table(path, TableFormat::Parquet)
+    }
+
+    /// Writes the data to an Arrow IPC file in the long format
+    ///
+    /// Each row is the frequency response of an output channel to an input channel at a frequency,
+    /// the rows are grouped by channels pairs and sorted by frequencies.
+    /// The frequencies, the magnitude and the phase are written with the data file conventions,
+    /// whatever the representation, and the conventions are saved in the table schema metadata
+    #[cfg(feature = "tabular")]
+    pub fn dump_to_arrow(self, path: impl AsRef<Path>) -> Result<()> {
+        self.dump_to_table(path, TableFormat::ArrowIpc)
+    }
+
+    #[cfg(feature = "tabular")]
+    fn dump_to_table(self, path: impl AsRef<Path>, format: TableFormat) -> Result<()> {
+        let (outputs, inputs) = self.channels();
+        let mut records = self.frequency_response().into_owned();
+        records.sort_by(|a, b| a.frequency().total_cmp(&b.frequency()));
+        let mut exporter = Exporter::new(&self);
+        exporter.representation = Representation::Both;
+        let columns = records
+            .iter()
+            .map(|data| {
+                let complex = data
+                    .complex_matrix()
+                    .ok_or(TransferFunctionDataError::Representation(data.frequency()))?;
+                let FrequencyResponseData {
+                    frequency,
+                    magnitude: Some(magnitude),
+                    phase: Some(phase),
+                    real: Some(re),
+                    imaginary: Some(im),
+                } = exporter.export(FrequencyResponseData::new(data.frequency(), complex))
+                else {
+                    unreachable!("the frequency response has both representations")
+                };
+                Ok((frequency, magnitude, phase, re, im))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let metadata = std::collections::HashMap::from(
+            [
+                ("fem", self.fem.clone()),
+                (
+                    "modal_damping_model",
+                    self.modal_damping_model.name().to_string(),
+                ),
+                ("frequency_unit", self.frequency_unit.name().to_string()),
+                ("magnitude_unit", self.magnitude_unit.name().to_string()),
+                ("phase_unit", self.phase_unit.name().to_string()),
+                ("unwrapped_phase", self.unwrapped_phase.to_string()),
+            ]
+            .map(|(key, value)| (key.to_string(), value)),
+        );
+        let (outputs, columns) = (&outputs, &columns);
+        let rows = inputs
+            .iter()
+            .enumerate()
+            .flat_map(move |(j, (input, input_channel))| {
+                outputs
+                    .iter()
+                    .enumerate()
+                    .flat_map(move |(i, (output, output_channel))| {
+                        columns
+                            .iter()
+                            .map(move |(frequency, magnitude, phase, re, im)| Row {
+                                frequency: *frequency,
+                                output,
+                                output_channel: *output_channel,
+                                input,
+                                input_channel: *input_channel,
+                                magnitude: magnitude[(i, j)],
+                                phase: phase[(i, j)],
+                                re: re[(i, j)],
+                                im: im[(i, j)],
+                            })
+                    })
+            });
+        tabular::write(path, format, metadata, rows)?;
         Ok(())
     }
 
+    /// Reads the data from either a pickle or a Matlab file written with [TransferFunctionData::dump]
+    ///
+    /// The file extension, "pkl" or "mat", sets the file type, a pickle file may also be a stream.
+    /// The frequency response is converted to frequencies in Hz, linear magnitude and phase in radians
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        const EXTENSIONS: &str = r#""mat" or "pkl""#;
+        let path = path.as_ref();
+        match path.extension() {
+            Some(ext) if ext == "pkl" => {
+                let (data, streamed) = stream::read(path)
+                    .map_err(|e| TransferFunctionDataError::ReadPkl(Box::new(e)))?;
+                Ok(data.extend_response(streamed))
+            }
+            Some(ext) if ext == "mat" => Ok(Self::load_from_mat(path)?.import()),
+            Some(ext) => Err(TransferFunctionDataError::DataFileExtension(
+                ext.to_string_lossy().into_owned(),
+                EXTENSIONS,
+            )),
+            None => Err(TransferFunctionDataError::MissingFileExtension(EXTENSIONS)),
+        }
+    }
+
+    /// Reads the data from a Matlab file written with [TransferFunctionData::dump_to_mat]
+    ///
+    /// The frequency response is left in the data file conventions.
+    /// The strings of a file without [MAT_STRINGS] are [inferred](MatStrings::infer)
+    /// and its `modal_damping_coefficient`, if any, is read as a constant damping schedule
+    fn load_from_mat(path: impl AsRef<Path>) -> Result<Self> {
+        use matio_rs::{MatFile, MatioError, MayBeInto};
+        let file = MatFile::load(path)?;
+        let tf = file.read("transfer_functions")?;
+        let strings: MatStrings = match file.var::<_, String>(MAT_STRINGS) {
+            Ok(strings) => serde_json::from_str(&strings)?,
+            Err(MatioError::MatVarRead(_)) => MatStrings::infer(&tf)?,
+            Err(e) => return Err(e.into()),
+        };
+        let layout = parse_name("layout", strings.layout, Layout::name)?;
+        let response = mat_field(&tf, "frequency_response")?;
+        let frequency_response = match layout {
+            Layout::Records => FrequencyResponseLayout::Records(
+                response
+                    .field("data")?
+                    .into_iter()
+                    .map(|data| -> Result<_> {
+                        Ok(FrequencyResponseData {
+                            frequency: mat_field(data, "frequency")?.maybe_into()?,
+                            magnitude: mat_matrix(data, "magnitude")?,
+                            phase: mat_matrix(data, "phase")?,
+                            real: mat_matrix(data, "real")?,
+                            imaginary: mat_matrix(data, "imaginary")?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            Layout::Columnar => FrequencyResponseLayout::Columnar(ColumnarFrequencyResponse {
+                frequency: mat_field(response, "frequency")?.maybe_into()?,
+                magnitude: mat_array(response, "magnitude")?,
+                phase: mat_array(response, "phase")?,
+                real: mat_array(response, "real")?,
+                imaginary: mat_array(response, "imaginary")?,
+            }),
+        };
+        let fem_eigen_frequency_range: Vec<f64> =
+            mat_field(&tf, "fem_eigen_frequency_range")?.maybe_into()?;
+        let fem_io = |name: &str, names: Vec<String>| -> Result<Vec<FemIo>> {
+            if names.is_empty() {
+                return Ok(vec![]);
+            }
+            let sizes: Vec<u64> = mat_field(&tf, &format!("{name}_size"))?.maybe_into()?;
+            let mut channels = match mat_optional_field(&tf, &format!("{name}_channels"))? {
+                Some(channels) => MayBeInto::<Vec<u64>>::maybe_into(channels)?,
+                None => vec![],
+            }
+            .into_iter()
+            .map(|channel| channel as usize);
+            Ok(names
+                .into_iter()
+                .zip(sizes)
+                .map(|(name, size)| FemIo {
+                    name,
+                    size: size as usize,
+                    channels: channels.by_ref().take(size as usize).collect(),
+                })
+                .collect())
+        };
+        let shard = strings
+            .shard
+            .map(|shard| {
+                shard
+                    .parse()
+                    .map_err(|_| TransferFunctionDataError::MatlabValue("shard", shard.clone()))
+            })
+            .transpose()?;
+        Ok(Self {
+            fem: strings.fem,
+            inputs: strings.inputs,
+            outputs: strings.outputs,
+            modal_damping_model: parse_name(
+                "modal damping model",
+                strings.modal_damping_model,
+                DampingModel::name,
+            )?,
+            modal_damping: match mat_optional_field(&tf, "modal_damping_coefficient")? {
+                Some(z) => DampingSchedule::Constant(z.maybe_into()?),
+                None => damping_from_mat(
+                    strings.modal_damping_schedule,
+                    mat_field(&tf, "modal_damping")?,
+                )?,
+            },
+            fem_eigen_frequency_range: match fem_eigen_frequency_range.as_slice() {
+                &[min, max] => (min, max),
+                _ => {
+                    return Err(TransferFunctionDataError::MatlabValue(
+                        "FEM eigen frequency range",
+                        format!("{fem_eigen_frequency_range:?}"),
+                    ));
+                }
+            },
+            fem_inputs: fem_io("fem_inputs", strings.fem_inputs)?,
+            fem_outputs: fem_io("fem_outputs", strings.fem_outputs)?,
+            structural: strings.structural,
+            shard,
+            frequency_unit: parse_name(
+                "frequency unit",
+                strings.frequency_unit,
+                FrequencyUnit::name,
+            )?,
+            representation: parse_name(
+                "representation",
+                strings.representation,
+                Representation::name,
+            )?,
+            magnitude_unit: parse_name(
+                "magnitude unit",
+                strings.magnitude_unit,
+                MagnitudeUnit::name,
+            )?,
+            phase_unit: parse_name("phase unit", strings.phase_unit, PhaseUnit::name)?,
+            unwrapped_phase: match mat_optional_field(&tf, "unwrapped_phase")? {
+                Some(unwrapped_phase) => MayBeInto::<u8>::maybe_into(unwrapped_phase)? != 0,
+                None => false,
+            },
+            layout,
+            frequency_response,
+        })
+    }
+
+    /// Returns the FEM name
+    pub fn fem(&self) -> &str {
+        &self.fem
+    }
+
+    /// Returns the inputs names
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    /// Returns the outputs names
+    pub fn outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    /// Returns the FEM output name and channel index of each row of the frequency response
+    /// and the FEM input name and channel index of each column
+    ///
+    /// The rows and the columns are named `output` and `input` with their index
+    /// if the FEM outputs or inputs are unknown, e.g. for the linear optical model outputs
+    pub fn channels(&self) -> (Vec<(String, usize)>, Vec<(String, usize)>) {
+        let (ny, nu) = self.frequency_response.size().unwrap_or_default();
+        (
+            io_channels(&self.fem_outputs, ny, "output"),
+            io_channels(&self.fem_inputs, nu, "input"),
+        )
+    }
+
+    /// Returns the modal damping model and schedule
+    pub fn modal_damping(&self) -> (DampingModel, &DampingSchedule) {
+        (self.modal_damping_model, &self.modal_damping)
+    }
+
+    /// Returns the FEM eigen frequency range \[Hz\]
+    pub fn fem_eigen_frequency_range(&self) -> (f64, f64) {
+        self.fem_eigen_frequency_range
+    }
+
+    /// Returns the shard of the frequency grid the data belongs to, if any
+    pub fn frequency_shard(&self) -> Option<Shard> {
+        self.shard
+    }
+
+    /// Returns the frequency response in the records layout
+    pub fn frequency_response(&self) -> Cow<'_, [FrequencyResponseData<DMatrix<Complex<f64>>>]> {
+        match &self.frequency_response {
+            FrequencyResponseLayout::Records(records) => Cow::Borrowed(records.deref()),
+            FrequencyResponseLayout::Columnar(columnar) => {
+                Cow::Owned(FrequencyResponseVec::from(columnar.clone()).0)
+            }
+        }
+    }
+
+    /// Returns the frequencies of the frequency response
+    pub fn frequencies(&self) -> Vec<f64> {
+        match &self.frequency_response {
+            FrequencyResponseLayout::Records(records) => records.frequencies(),
+            FrequencyResponseLayout::Columnar(columnar) => columnar.frequency.clone(),
+        }
+    }
+
+    /// Returns the complex frequency response of the output channel `i` to the input channel `j`
+    /// at each frequency
+    ///
+    /// Returns `None` if the channels are outside the frequency response matrix
+    pub fn response(&self, i: usize, j: usize) -> Option<Vec<Complex<f64>>> {
+        self.frequency_response()
+            .iter()
+            .map(|data| data.get(i, j))
+            .collect()
+    }
+
+    /// Checks that the FEM, the inputs, the outputs, the structural model
+    /// and the data files conventions of both data match
+    ///
+    /// The structural model fingerprint covers the modal damping, the eigen frequency window,
+    /// the static gain corrections, the channels selections and the modal model file
+    pub fn check_metadata(&self, other: &Self) -> Result<()> {
+        if self.fem != other.fem {
+            return Err(TransferFunctionDataError::Metadata("FEM"));
+        }
+        if self.inputs != other.inputs {
+            return Err(TransferFunctionDataError::Metadata("inputs"));
+        }
+        if self.outputs != other.outputs {
+            return Err(TransferFunctionDataError::Metadata("outputs"));
+        }
+        if self.modal_damping_model != other.modal_damping_model
+            || self.modal_damping != other.modal_damping
+        {
+            return Err(TransferFunctionDataError::Metadata("modal damping"));
+        }
+        if self.fem_eigen_frequency_range != other.fem_eigen_frequency_range {
+            return Err(TransferFunctionDataError::Metadata(
+                "FEM eigen frequency range",
+            ));
+        }
+        if self.structural != other.structural {
+            return Err(TransferFunctionDataError::Metadata("structural model"));
+        }
+        if self.frequency_unit != other.frequency_unit {
+            return Err(TransferFunctionDataError::Metadata("frequency unit"));
+        }
+        if self.representation != other.representation {
+            return Err(TransferFunctionDataError::Metadata("representation"));
+        }
+        if self.magnitude_unit != other.magnitude_unit || self.phase_unit != other.phase_unit {
+            return Err(TransferFunctionDataError::Metadata(
+                "magnitude and phase units",
+            ));
+        }
+        if self.unwrapped_phase != other.unwrapped_phase {
+            return Err(TransferFunctionDataError::Metadata("phase unwrapping"));
+        }
+        if self.layout != other.layout {
+            return Err(TransferFunctionDataError::Metadata("layout"));
+        }
+        Ok(())
+    }
+
+    /// Sets the units of the frequencies written to the data files
+    pub fn frequency_unit(self, frequency_unit: FrequencyUnit) -> Self {
+        Self {
+            frequency_unit,
+            ..self
+        }
+    }
+
+    /// Sets the representation of the frequency response written to the data files
+    pub fn representation(self, representation: Representation) -> Self {
+        Self {
+            representation,
+            ..self
+        }
+    }
+
+    /// Sets the units of the magnitude and of the phase written to the data files
+    pub fn polar_units(self, magnitude_unit: MagnitudeUnit, phase_unit: PhaseUnit) -> Self {
+        Self {
+            magnitude_unit,
+            phase_unit,
+            ..self
+        }
+    }
+
+    /// Unwraps the phase along the sorted frequencies in the data files
+    pub fn unwrapped_phase(self, unwrapped_phase: bool) -> Self {
+        Self {
+            unwrapped_phase,
+            ..self
+        }
+    }
+
+    /// Sets the data files conventions to the ones of the computed frequency responses:
+    /// frequencies in Hz, both representations, linear magnitude and wrapped phase in radians
+    pub(crate) fn raw(self) -> Self {
+        Self {
+            frequency_unit: FrequencyUnit::Hz,
+            representation: Representation::Both,
+            magnitude_unit: MagnitudeUnit::Linear,
+            phase_unit: PhaseUnit::Radian,
+            unwrapped_phase: false,
+            layout: Layout::Records,
+            ..self
+        }
+    }
+
+    /// Sets the layout of the frequency response in the data files
+    pub fn layout(self, layout: Layout) -> Self {
+        Self { layout, ..self }
+    }
+
+    /// Converts the frequency response to the data files conventions and layout
+    ///
+    /// The frequency response is sorted by increasing frequencies if the phase is unwrapped
+    pub(crate) fn export(mut self) -> Self {
+        let mut exporter = Exporter::new(&self);
+        let mut data = std::mem::take(self.frequency_response.records_mut());
+        if self.unwrapped_phase {
+            data.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+        }
+        let records: FrequencyResponseVec<_> =
+            data.into_iter().map(|data| exporter.export(data)).collect();
+        self.frequency_response = match self.layout {
+            Layout::Records => FrequencyResponseLayout::Records(records),
+            Layout::Columnar => FrequencyResponseLayout::Columnar((&records).into()),
+        };
+        self
+    }
+
+    /// Converts the frequency response from the data files conventions
+    /// to frequencies in Hz, linear magnitude and phase in radians
+    pub(crate) fn import(mut self) -> Self {
+        let exporter = Exporter::new(&self);
+        let records = self.frequency_response.records_mut();
+        *records = std::mem::take(records)
+            .into_iter()
+            .map(|data| exporter.import(data))
+            .collect();
+        self
+    }
+
+    /// Sets the shard of the frequency grid the data belongs to
+    pub fn shard(self, shard: Option<Shard>) -> Self {
+        Self { shard, ..self }
+    }
+
+    /// Merges the data of the shards of a frequency grid
+    ///
+    /// The metadata of all the shards, including the structural model fingerprint
+    /// and the data files conventions, must match and the shards must be complete
+    pub fn merge(mut shards: Vec<Self>) -> Result<Self> {
+        let Some(first) = shards.first() else {
+            return Err(TransferFunctionDataError::Shards(
+                "no shards to merge".into(),
+            ));
+        };
+        for shard in &shards[1..] {
+            first.check_metadata(shard)?;
+        }
+        if shards.iter().any(|data| data.shard.is_some()) {
+            let mut indices = shards
+                .iter()
+                .map(|data| data.shard.map(|shard| (shard.n, shard.k)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| TransferFunctionDataError::Shards("missing shard index".into()))?;
+            indices.sort();
+            let n = indices[0].0;
+            if indices != (1..=n).map(|k| (n, k)).collect::<Vec<_>>() {
+                return Err(TransferFunctionDataError::Shards(format!(
+                    "expected shards 1/{n} to {n}/{n}, found {}",
+                    indices
+                        .iter()
+                        .map(|(n, k)| format!("{k}/{n}"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+            shards.sort_by_key(|data| data.shard.map(|shard| shard.k));
+        }
+        let frequency_response = shards
+            .iter_mut()
+            .flat_map(|data| std::mem::take(data.frequency_response.records_mut()))
+            .collect();
+        let first = shards.swap_remove(0);
+        Ok(Self {
+            shard: None,
+            frequency_response: FrequencyResponseLayout::Records(frequency_response),
+            ..first
+        })
+    }
+
+    /// Appends frequency responses to the data
+    pub fn extend_response(
+        mut self,
+        data: impl IntoIterator<Item = FrequencyResponseData<DMatrix<Complex<f64>>>>,
+    ) -> Self {
+        self.frequency_response.records_mut().extend(data);
+        self
+    }
+
     /// Adds the [frequency response](FrequencyResponseVec) to the data
     pub fn add_response(
         self,
         frequency_response: FrequencyResponseVec<DMatrix<Complex<f64>>>,
     ) -> Self {
         Self {
-            frequency_response,
+            frequency_response: FrequencyResponseLayout::Records(frequency_response),
             ..self
         }
     }
 
     /// Adds additional data from the structural model
+    ///
+    /// The inputs and outputs of the structural model are used if none have been set,
+    /// the FEM outputs are not recorded if the outputs are the linear optical model outputs
     pub fn add_structural(self, structural: &Structural) -> Self {
+        let fem_io = |names: &[String], sizes: &[usize], channels: &[Vec<usize>]| -> Vec<FemIo> {
+            names
+                .iter()
+                .zip(sizes)
+                .enumerate()
+                .map(|(i, (name, &size))| FemIo {
+                    name: name.clone(),
+                    size,
+                    channels: channels.get(i).cloned().unwrap_or_default(),
+                })
+                .collect()
+        };
         let c = 0.5 * f64::consts::FRAC_1_PI;
+        let inputs = if self.inputs.is_empty() {
+            structural.inputs.clone()
+        } else {
+            self.inputs
+        };
+        let outputs = if self.outputs.is_empty() {
+            structural.outputs.clone()
+        } else {
+            self.outputs
+        };
         Self {
-            fem_eigen_frequency_range: (structural.w[0] * c, *structural.w.last().unwrap() * c),
+            fem: structural.model.clone(),
+            inputs,
+            outputs,
+            fem_inputs: fem_io(
+                &structural.inputs,
+                &structural.inputs_size,
+                &structural.inputs_channels,
+            ),
+            fem_outputs: if structural.optical_senses.is_none() {
+                fem_io(
+                    &structural.outputs,
+                    &structural.outputs_size,
+                    &structural.outputs_channels,
+                )
+            } else {
+                vec![]
+            },
+            modal_damping_model: structural.damping_model,
+            modal_damping: structural.damping.clone(),
+            fem_eigen_frequency_range: structural
+                .w
+                .first()
+                .zip(structural.w.last())
+                .map(|(w_min, w_max)| (w_min * c, w_max * c))
+                .unwrap_or_default(),
+            structural: Some(structural.fingerprint.clone()),
             ..self
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{structural::StructuralBuilder, test_utils::TempFile};
+
+    /// Returns the `ny×nu` frequency responses `h(frequency, i, j)` at the given frequencies
+    fn records(
+        frequencies: &[f64],
+        (ny, nu): (usize, usize),
+        h: impl Fn(f64, usize, usize) -> Complex<f64>,
+    ) -> FrequencyResponseVec<DMatrix<Complex<f64>>> {
+        frequencies
+            .iter()
+            .map(|&frequency| {
+                FrequencyResponseData::new(
+                    frequency,
+                    DMatrix::from_fn(ny, nu, |i, j| h(frequency, i, j)),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unwrapped_phase() {
+        let data = |theta: f64| {
+            FrequencyResponseData::new(
+                theta,
+                DMatrix::from_element(1, 1, Complex::from_polar(10., theta)),
+            )
+        };
+        let header = TransferFunctionData::default()
+            .polar_units(MagnitudeUnit::Decibel, PhaseUnit::Degree)
+            .unwrapped_phase(true);
+        let mut exporter = Exporter::new(&header);
+        for theta in [0., 2., 4., 6.] {
+            let data = exporter.export(data(theta));
+            assert!((data.magnitude.as_ref().unwrap()[0] - 20.).abs() < 1e-12);
+            assert!((data.phase.as_ref().unwrap()[0] - theta.to_degrees()).abs() < 1e-9);
+            let data = exporter.import(data);
+            assert!((data.magnitude.as_ref().unwrap()[0] - 10.).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn columnar() {
+        let records: FrequencyResponseVec<_> = records(&[1., 2., 3.], (2, 3), |nu, i, j| {
+            Complex::new(nu, (i * 3 + j) as f64)
+        })
+        .iter()
+        .cloned()
+        .map(|data| data.into_representation(Representation::Complex))
+        .collect();
+        let columnar = ColumnarFrequencyResponse::from(&records);
+        assert_eq!(columnar.frequency, vec![1., 2., 3.]);
+        assert!(columnar.magnitude.is_none());
+        let imaginary = columnar.imaginary.as_ref().unwrap();
+        assert_eq!((imaginary.len(), imaginary[0].len()), (2, 3));
+        assert_eq!(imaginary[1][2], vec![5.; 3]);
+        let (name, dims, data) = &columnar.arrays()[0];
+        assert_eq!((*name, dims.as_slice()), ("real", [2, 3, 3].as_slice()));
+        assert_eq!(data[6..12], [2.; 6]);
+        let records_back = FrequencyResponseVec::from(columnar);
+        assert_eq!(records_back[1].real, records[1].real);
+    }
+
+    #[test]
+    fn load() {
+        let records = records(&[1., 2., 3.], (2, 3), |nu, i, j| {
+            Complex::new(nu, 1. + (i * 3 + j) as f64)
+        });
+        let data = TransferFunctionData::default()
+            .frequency_unit(FrequencyUnit::RadPerSecond)
+            .polar_units(MagnitudeUnit::Decibel, PhaseUnit::Degree)
+            .add_response(records);
+        for (file, data) in [
+            ("load.pkl", data.clone()),
+            (
+                "load.mat",
+                data.representation(Representation::Complex)
+                    .layout(Layout::Columnar),
+            ),
+        ] {
+            let path = TempFile::new(file);
+            data.dump(&path).unwrap();
+            let data = TransferFunctionData::load(&path).unwrap();
+            assert_eq!(data.modal_damping().1, &DampingSchedule::default());
+            let nu = data.frequencies();
+            assert!(
+                nu.iter()
+                    .zip([1., 2., 3.])
+                    .all(|(nu, expected)| (nu - expected).abs() < 1e-12)
+            );
+            let h = data.response(1, 2).unwrap();
+            assert!(
+                h.iter()
+                    .zip(&nu)
+                    .all(|(h, nu)| (h - Complex::new(*nu, 6.)).norm() < 1e-9)
+            );
+            assert!(data.response(2, 0).is_none());
+        }
+    }
+
+    #[test]
+    fn npz() {
+        let records = records(&[1., 2.], (2, 3), |nu, i, j| {
+            Complex::new(nu, (i * 3 + j) as f64)
+        });
+        let path = TempFile::new("npz.npz");
+        TransferFunctionData {
+            inputs: vec!["OSS_Harpoint_delta_F".into()],
+            ..Default::default()
+        }
+        .representation(Representation::Complex)
+        .add_response(records)
+        .dump(&path)
+        .unwrap();
+        let mut npz = npyz::npz::NpzArchive::open(&path).unwrap();
+        let inputs = npz.by_name("inputs").unwrap().unwrap();
+        assert_eq!(
+            inputs.into_vec::<String>().unwrap(),
+            vec!["OSS_Harpoint_delta_F".to_string()]
+        );
+        assert!(npz.by_name("magnitude").unwrap().is_none());
+        let imaginary = npz.by_name("imaginary").unwrap().unwrap();
+        assert_eq!(imaginary.shape(), &[2, 3, 2]);
+        assert_eq!(imaginary.order(), npyz::Order::Fortran);
+        let imaginary = imaginary.into_vec::<f64>().unwrap();
+        // imaginary[1][2][k] = 5
+        assert_eq!((imaginary[5], imaginary[11]), (5., 5.));
+    }
+
+    #[test]
+    fn uff() {
+        let records = records(&[2., 1.], (3, 2), |nu, _, _| Complex::new(nu, 1.));
+        let data = TransferFunctionData {
+            outputs: vec!["OSS_M1_lcl[6..8]".into()],
+            fem_inputs: vec![FemIo {
+                name: "OSS_Harpoint_delta_F".into(),
+                size: 2,
+                ..Default::default()
+            }],
+            fem_outputs: vec![
+                FemIo {
+                    name: "OSS_M1_lcl".into(),
+                    size: 2,
+                    channels: vec![6, 7],
+                },
+                FemIo {
+                    name: "OSS_M1_edge_sensors".into(),
+                    size: 1,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+        .frequency_unit(FrequencyUnit::RadPerSecond)
+        .polar_units(MagnitudeUnit::Decibel, PhaseUnit::Degree)
+        .add_response(records);
+        let (outputs, inputs) = data.channels();
+        assert_eq!(outputs[1], ("OSS_M1_lcl".to_string(), 7));
+        assert_eq!(outputs[2], ("OSS_M1_edge_sensors".to_string(), 0));
+        assert_eq!(inputs[1], ("OSS_Harpoint_delta_F".to_string(), 1));
+        let path = TempFile::new("uff.uff");
+        data.dump(&path).unwrap();
+        let uff = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(uff.matches("\n    58\n").count(), 6);
+        // the frequencies are sorted in Hz and the complex values are left in linear units
+        assert!(uff.contains("  1.00000E+00  1.000000000000E+00  1.000000000000E+00\n"));
+        // the node numbers of the edge sensors follow the M1 local node numbers
+        assert!(uff.contains(" OSS_M1_edg         9   0 "));
+    }
+
+    #[test]
+    #[cfg(feature = "tabular")]
+    fn parquet() {
+        use arrow_array::{Float64Array, StringArray, UInt64Array};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+        let records = records(&[2., 1.], (1, 2), |_, _, j| {
+            Complex::new(0., (j + 1) as f64)
+        });
+        let path = TempFile::new("parquet.parquet");
+        TransferFunctionData {
+            fem_inputs: vec![FemIo {
+                name: "OSS_Harpoint_delta_F".into(),
+                size: 2,
+                channels: vec![3, 4],
+            }],
+            ..Default::default()
+        }
+        .representation(Representation::Complex)
+        .polar_units(MagnitudeUnit::Decibel, PhaseUnit::Degree)
+        .add_response(records)
+        .dump(&path)
+        .unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(builder.schema().metadata()["magnitude_unit"], "dB");
+        let batch = builder.build().unwrap().next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 4);
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        let frequency = column("frequency");
+        let frequency = frequency.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(frequency.values(), &[1., 2., 1., 2.]);
+        let input = column("input");
+        let input = input.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(input.value(3), "OSS_Harpoint_delta_F");
+        let output = column("output");
+        let output = output.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(output.value(0), "output");
+        let input_channel = column("input_channel");
+        let input_channel = input_channel
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(input_channel.values(), &[3, 3, 4, 4]);
+        let magnitude = column("magnitude");
+        let magnitude = magnitude.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert!((magnitude.value(2) - 20. * 2f64.log10()).abs() < 1e-12);
+        let phase = column("phase");
+        let phase = phase.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert!((phase.value(0) - 90.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn baseline_pickle() {
+        // data file written before the damping schedules and the complex representation
+        #[derive(Serialize)]
+        struct Record {
+            frequency: f64,
+            magnitude: DMatrix<f64>,
+            phase: DMatrix<f64>,
+        }
+        #[derive(Serialize)]
+        struct Baseline {
+            fem: String,
+            inputs: Vec<String>,
+            outputs: Vec<String>,
+            modal_damping_coefficient: f64,
+            fem_eigen_frequency_range: (f64, f64),
+            frequency_response: Vec<Record>,
+        }
+        let path = TempFile::new("baseline.pkl");
+        serde_pickle::to_writer(
+            &mut File::create(&path).unwrap(),
+            &Baseline {
+                fem: "baseline".into(),
+                inputs: vec!["oss_harpoint_delta_f".into()],
+                outputs: vec!["oss_m1_lcl".into()],
+                modal_damping_coefficient: 0.005,
+                fem_eigen_frequency_range: (0., 100.),
+                frequency_response: vec![Record {
+                    frequency: 1.,
+                    magnitude: DMatrix::from_element(1, 1, 2.),
+                    phase: DMatrix::from_element(1, 1, 0.5),
+                }],
+            },
+            Default::default(),
+        )
+        .unwrap();
+        let data = TransferFunctionData::load(&path).unwrap();
+        assert_eq!(
+            data.modal_damping(),
+            (DampingModel::default(), &DampingSchedule::Constant(0.005))
+        );
+        assert_eq!(data.fem_eigen_frequency_range(), (0., 100.));
+        let h = data.response(0, 0).unwrap();
+        assert!((h[0] - Complex::from_polar(2., 0.5)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn baseline_mat() {
+        // data file written before the damping schedules and the strings variable
+        use matio_rs::{Mat, MatFile, MayBeFrom};
+        let path = TempFile::new("baseline.mat");
+        let data = vec![
+            Mat::maybe_from(
+                "data",
+                vec![
+                    Mat::maybe_from("frequency", 1f64).unwrap(),
+                    Mat::maybe_from("magnitude", DMatrix::from_element(1, 1, 2f64)).unwrap(),
+                    Mat::maybe_from("phase", DMatrix::from_element(1, 1, 0.5f64)).unwrap(),
+                ],
+            )
+            .unwrap(),
+        ];
+        let data_iter = Box::new(data.into_iter()) as Box<dyn Iterator<Item = Mat>>;
+        let fields = vec![
+            Mat::maybe_from("fem", "baseline").unwrap(),
+            Mat::maybe_from("inputs", vec!["oss_harpoint_delta_f".to_string()]).unwrap(),
+            Mat::maybe_from("outputs", vec!["oss_m1_lcl".to_string()]).unwrap(),
+            Mat::maybe_from("modal_damping_coefficient", 0.005).unwrap(),
+            Mat::maybe_from("fem_eigen_frequency_range", (0., 100.)).unwrap(),
+            Mat::maybe_from("frequency_response", vec![data_iter]).unwrap(),
+        ];
+        MatFile::save(&path)
+            .unwrap()
+            .write(Mat::maybe_from("transfer_functions", fields).unwrap());
+        let data = TransferFunctionData::load(&path).unwrap();
+        assert_eq!(
+            data.modal_damping(),
+            (DampingModel::default(), &DampingSchedule::Constant(0.005))
+        );
+        assert_eq!(data.fem_eigen_frequency_range(), (0., 100.));
+        assert_eq!(data.representation, Representation::Polar);
+        let h = data.response(0, 0).unwrap();
+        assert!((h[0] - Complex::from_polar(2., 0.5)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn metadata() {
+        let data = |builder: StructuralBuilder| {
+            TransferFunctionData::default().add_structural(&Structural {
+                fingerprint: builder.fingerprint(),
+                ..Default::default()
+            })
+        };
+        let builder = || Structural::builder(vec!["u".into()], vec!["y".into()]);
+        let reference = data(builder());
+        assert!(reference.check_metadata(&data(builder())).is_ok());
+        for other in [
+            builder().enable_residual_correction(),
+            builder().enable_static_gain_mismatch_compensation(Some(1e-3)),
+            builder().min_eigen_frequency(Some(1.)),
+            builder().input_channels("u", 0..2),
+        ] {
+            assert!(matches!(
+                reference.check_metadata(&data(other)),
+                Err(TransferFunctionDataError::Metadata("structural model"))
+            ));
+        }
+        assert!(matches!(
+            reference.check_metadata(
+                &reference
+                    .clone()
+                    .polar_units(MagnitudeUnit::Decibel, PhaseUnit::Degree)
+            ),
+            Err(TransferFunctionDataError::Metadata(
+                "magnitude and phase units"
+            ))
+        ));
+    }
+
+    #[test]
+    fn merge() {
+        let shard = |k: usize, residual_correction: bool| {
+            let builder = Structural::builder(vec!["u".into()], vec!["y".into()]);
+            let builder = if residual_correction {
+                builder.enable_residual_correction()
+            } else {
+                builder
+            };
+            TransferFunctionData::default()
+                .add_structural(&Structural {
+                    fingerprint: builder.fingerprint(),
+                    ..Default::default()
+                })
+                .shard(Some(Shard { k, n: 2 }))
+                .add_response(records(&[k as f64], (1, 1), |_, _, _| Complex::new(1., 0.)))
+        };
+        let mut merged =
+            TransferFunctionData::merge(vec![shard(2, false), shard(1, false)]).unwrap();
+        let nu: Vec<_> = merged
+            .frequency_response
+            .records_mut()
+            .iter()
+            .map(|data| data.frequency)
+            .collect();
+        assert_eq!(nu, vec![1., 2.]);
+        assert!(matches!(
+            TransferFunctionData::merge(vec![shard(1, false), shard(2, true)]),
+            Err(TransferFunctionDataError::Metadata("structural model"))
+        ));
+    }
+
+    #[test]
+    fn representation() {
+        let data = FrequencyResponseData::new(1., Complex::new(3., 4.));
+        assert!(data.magnitude.is_none() && data.phase.is_none());
+        let complex = data.clone().into_representation(Representation::Complex);
+        assert!(complex.magnitude.is_none() && complex.phase.is_none());
+        let bytes = serde_pickle::to_vec(&complex, Default::default()).unwrap();
+        let complex: FrequencyResponseData<Complex<f64>> =
+            serde_pickle::from_slice(&bytes, Default::default()).unwrap();
+        assert_eq!((complex.real, complex.imaginary), (Some(3.), Some(4.)));
+        let polar = data.into_representation(Representation::Polar);
+        assert!(polar.real.is_none() && polar.imaginary.is_none());
+        assert_eq!(polar.magnitude, Some(5.));
+        let both = polar.into_representation(Representation::Both);
+        assert!((both.real.unwrap() - 3.).abs() < 1e-12);
+        assert!((both.imaginary.unwrap() - 4.).abs() < 1e-12);
+    }
+}
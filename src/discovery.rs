@@ -0,0 +1,175 @@
+//! FEM inputs and outputs discovery
+//!
+//! Matches the inputs and outputs given on the command line
+//! against the inputs and outputs of either the compiled FEM or
+//! a FEM loaded at runtime
+
+use std::fmt::Display;
+
+use gmt_fem::FEM;
+use serde::{Deserialize, Serialize};
+
+/// FEM input or output
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FemIo {
+    /// FEM name
+    pub name: String,
+    /// number of channels
+    pub size: usize,
+    /// FEM channels indices of the channels selection, empty if all the channels are selected
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<usize>,
+}
+impl FemIo {
+    /// Returns the FEM channel index of the `k`th channel
+    pub fn channel(&self, k: usize) -> usize {
+        self.channels.get(k).copied().unwrap_or(k)
+    }
+}
+
+/// FEM inputs and outputs
+#[derive(Debug, Default, Clone)]
+pub struct FemIoNames {
+    pub inputs: Vec<FemIo>,
+    pub outputs: Vec<FemIo>,
+}
+impl From<&FEM> for FemIoNames {
+    fn from(fem: &FEM) -> Self {
+        Self {
+            inputs: fem
+                .inputs
+                .iter()
+                .filter_map(|x| x.as_ref())
+                .map(|x| FemIo {
+                    name: x.name().to_string(),
+                    size: x.len(),
+                    ..Default::default()
+                })
+                .collect(),
+            outputs: fem
+                .outputs
+                .iter()
+                .filter_map(|x| x.as_ref())
+                .map(|x| FemIo {
+                    name: x.name().to_string(),
+                    size: x.len(),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the `(aliases, name)` candidates of FEM inputs or outputs
+pub fn candidates(ios: &[FemIo]) -> Vec<(Vec<String>, String)> {
+    ios.iter()
+        .map(|io| (vec![io.name.clone()], io.name.clone()))
+        .collect()
+}
+
+/// Closest matches of a misspelled name
+#[derive(Debug, Default)]
+pub struct Suggestions(pub Vec<String>);
+impl Display for Suggestions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.0.is_empty() {
+            write!(f, ", did you mean: {}?", self.0.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Normalizes a name to lower case alphanumeric characters
+///
+/// FEM names (e.g. `OSS_Harpoint_delta_F`) and command line names (e.g. `oss-harpoint-delta-f`)
+/// have the same normalized form
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Levenshtein edit distance
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diag
+            } else {
+                1 + diag.min(above).min(row[j])
+            };
+            diag = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// Looks up `name` in the list of `(aliases, name)` candidates
+///
+/// Returns the candidate name or the 3 closest candidates names if there is no match
+pub fn lookup(name: &str, candidates: &[(Vec<String>, String)]) -> Result<String, Suggestions> {
+    let key = normalize(name);
+    let mut distances = vec![];
+    for (aliases, candidate) in candidates {
+        let distance = aliases
+            .iter()
+            .map(|alias| levenshtein(&key, &normalize(alias)))
+            .min()
+            .unwrap_or(usize::MAX);
+        if distance == 0 {
+            return Ok(candidate.clone());
+        }
+        distances.push((distance, candidate.clone()));
+    }
+    distances.sort();
+    let threshold = (key.len() / 3).max(2);
+    Err(Suggestions(
+        distances
+            .into_iter()
+            .filter(|(distance, _)| *distance <= threshold)
+            .take(3)
+            .map(|(_, candidate)| candidate)
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<(Vec<String>, String)> {
+        vec![
+            (
+                vec!["oss-harpoint-delta-f".into(), "OSS_Harpoint_delta_F".into()],
+                "OSS_Harpoint_delta_F".into(),
+            ),
+            (
+                vec!["ossm1-lcl".into(), "OSS_M1_lcl".into()],
+                "OSS_M1_lcl".into(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn exact() {
+        assert_eq!(
+            lookup("oss-harpoint-delta-f", &candidates()).unwrap(),
+            "OSS_Harpoint_delta_F"
+        );
+        assert_eq!(lookup("OSS_M1_lcl", &candidates()).unwrap(), "OSS_M1_lcl");
+    }
+
+    #[test]
+    fn typo() {
+        let Err(Suggestions(suggestions)) = lookup("oss-hardpoint-delta-f", &candidates()) else {
+            panic!("expected suggestions")
+        };
+        assert_eq!(suggestions, vec!["OSS_Harpoint_delta_F".to_string()]);
+    }
+}
@@ -3,21 +3,105 @@
 use indicatif::{ParallelProgressIterator, ProgressStyle};
 use num_complex::Complex;
 use rayon::prelude::*;
-use std::{f64::consts::PI, ops::Mul};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    f64::consts::PI,
+    fmt::Display,
+    fs::File,
+    io::{self, BufReader},
+    ops::Mul,
+    path::Path,
+    str::FromStr,
+};
 
-use crate::data::{Cartesian2Polar, FrequencyResponseData, FrequencyResponseVec};
+use crate::{
+    data::{Cartesian2Polar, FrequencyResponseData, FrequencyResponseVec},
+    table::{self, TableError},
+};
 
 const DPI: f64 = 2f64 * PI;
 
+/// Progress bar style of the frequency response computation
+pub(crate) fn progress_style() -> ProgressStyle {
+    ProgressStyle::with_template("|{bar} {pos}|")
+        .unwrap()
+        .progress_chars("-.-")
+}
+
 #[allow(non_camel_case_types)]
 pub type if64 = Complex<f64>;
 
+#[derive(Debug, thiserror::Error)]
+pub enum FrequenciesError {
+    #[error(r#"invalid shard "{0}", expected "k/N" with 1 ≤ k ≤ N"#)]
+    Shard(String),
+    #[error("the upper frequency ({upper}) must be greater than the lower frequency ({lower})")]
+    Interval { lower: f64, upper: f64 },
+    #[error("the logarithmic sampling requires positive frequencies, found [{lower},{upper}]")]
+    LogInterval { lower: f64, upper: f64 },
+    #[error("the sampling of an interval requires at least 2 samples, found {0}")]
+    Samples(usize),
+    #[error("there is no sampling frequencies")]
+    Empty,
+    #[error(r#"invalid frequencies specification "{0}""#)]
+    Spec(String),
+    #[error("failed to read frequencies file")]
+    IO(#[from] io::Error),
+    #[error("failed to read frequencies table")]
+    Table(#[from] TableError),
+    #[error("failed to read frequencies Matlab file")]
+    Matlab(#[from] matio_rs::MatioError),
+}
+
+/// Frequency units
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+pub enum FrequencyUnit {
+    /// Hertz
+    #[default]
+    #[serde(rename = "Hz")]
+    #[value(name = "hz")]
+    Hz,
+    /// radians per second
+    #[serde(rename = "rad/s")]
+    #[value(name = "rad-per-s")]
+    RadPerSecond,
+}
+impl FrequencyUnit {
+    /// Returns the unit symbol
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Hz => "Hz",
+            Self::RadPerSecond => "rad/s",
+        }
+    }
+    /// Converts a frequency in this unit to Hz
+    pub fn to_hz(&self, value: f64) -> f64 {
+        match self {
+            Self::Hz => value,
+            Self::RadPerSecond => value / DPI,
+        }
+    }
+    /// Converts a frequency in Hz to this unit
+    pub fn hz_to(&self, nu: f64) -> f64 {
+        match self {
+            Self::Hz => nu,
+            Self::RadPerSecond => nu * DPI,
+        }
+    }
+}
+impl Display for FrequencyUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 /// Frequency sampling options
 ///
-/// The frequencies units is Hz
+/// The frequencies units is Hz, unless converted with [Frequencies::into_hz]
 #[derive(Debug, Clone, clap::Subcommand)]
 #[command(
-    subcommand_help_heading = "Transfer functions sampling frequencies [Hz]",
+    subcommand_help_heading = "Transfer functions sampling frequencies [--frequency-unit]",
     subcommand_value_name = "SAMPLING FREQUENCIES"
 )]
 pub enum Frequencies {
@@ -46,6 +130,76 @@ pub enum Frequencies {
         #[arg(short, long)]
         values: Vec<f64>,
     },
+    /// adaptive sampling of the interval `[lower,upper]` starting from a logarithmic sampling with `n` samples
+    /// and the resonance frequencies within the interval,
+    /// the sampling is refined until the interpolation errors are less than the tolerances
+    Adaptive {
+        #[arg(short, long)]
+        lower: f64,
+        #[arg(short, long)]
+        upper: f64,
+        #[arg(short, default_value_t = 100)]
+        n: usize,
+        /// magnitude interpolation error tolerance \[dB\]
+        #[arg(long, default_value_t = 0.1)]
+        magnitude_tolerance: f64,
+        /// phase interpolation error tolerance \[deg\]
+        #[arg(long, default_value_t = 1.)]
+        phase_tolerance: f64,
+        /// maximum number of frequencies
+        #[arg(long, default_value_t = 10_000)]
+        max_n: usize,
+    },
+    /// frequencies read from a CSV or text table, a numpy (.npy) or a Matlab (.mat) file
+    File {
+        path: String,
+        /// name of the frequencies variable in the Matlab file
+        #[arg(long, default_value = "frequencies")]
+        variable: String,
+    },
+    /// sorted and de-duplicated union of the frequencies of several specifications:
+    /// `<value>`, `log-space:<lower>:<upper>:<n>`, `lin-space:<lower>:<upper>:<n>`,
+    /// `set:<value>,<value>,...` or `file:<path>`
+    Compose {
+        #[arg(required = true)]
+        specs: Vec<Frequencies>,
+    },
+}
+impl FromStr for Frequencies {
+    type Err = FrequenciesError;
+
+    /// Parses a frequencies specification of the [Frequencies::Compose] sampling
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec = || FrequenciesError::Spec(s.to_string());
+        let number = |x: &str| x.trim().parse::<f64>().map_err(|_| spec());
+        let space = |args: &str| match args.split(':').collect::<Vec<_>>().as_slice() {
+            [lower, upper, n] => Ok((
+                number(lower)?,
+                number(upper)?,
+                n.trim().parse::<usize>().map_err(|_| spec())?,
+            )),
+            _ => Err(spec()),
+        };
+        match s.trim().split_once(':') {
+            Some(("log-space", args)) => {
+                let (lower, upper, n) = space(args)?;
+                Ok(Self::LogSpace { lower, upper, n })
+            }
+            Some(("lin-space", args)) => {
+                let (lower, upper, n) = space(args)?;
+                Ok(Self::LinSpace { lower, upper, n })
+            }
+            Some(("set", args)) => Ok(Self::Set {
+                values: args.split(',').map(number).collect::<Result<_, _>>()?,
+            }),
+            Some(("file", path)) => Ok(Self::File {
+                path: path.trim().to_string(),
+                variable: "frequencies".to_string(),
+            }),
+            Some(_) => Err(spec()),
+            None => Ok(Self::Single { value: number(s)? }),
+        }
+    }
 }
 impl From<f64> for Frequencies {
     fn from(value: f64) -> Self {
@@ -70,12 +224,195 @@ impl From<&Self> for Frequencies {
     }
 }
 impl Frequencies {
+    /// Returns the frequencies \[Hz\]
+    ///
+    /// For the adaptive sampling, only the initial logarithmic sampling is returned,
+    /// the refinement is performed by [FrequencyResponse::frequency_response]
+    pub fn values(&self) -> Result<Vec<f64>, FrequenciesError> {
+        self.validate()?;
+        let values = match self {
+            Frequencies::Single { value } => vec![*value],
+            Frequencies::LogSpace { lower, upper, n } => {
+                let log_step = (upper.log10() - lower.log10()) / (n - 1) as f64;
+                (0..*n)
+                    .map(|i| 10f64.powf(lower.log10() + log_step * i as f64))
+                    .collect()
+            }
+            Frequencies::LinSpace { lower, upper, n } => {
+                let step = (upper - lower) / (n - 1) as f64;
+                (0..*n).map(|i| lower + step * i as f64).collect()
+            }
+            Frequencies::Set { values } => values.clone(),
+            Frequencies::Adaptive {
+                lower, upper, n, ..
+            } => Frequencies::logspace(*lower, *upper, *n).values()?,
+            Frequencies::File { path, variable } => read_frequencies(path, variable)?,
+            Frequencies::Compose { specs } => {
+                let mut values = specs
+                    .iter()
+                    .map(|spec| spec.values())
+                    .collect::<Result<Vec<_>, _>>()?
+                    .concat();
+                values.sort_by(f64::total_cmp);
+                values.dedup();
+                values
+            }
+        };
+        if values.is_empty() {
+            return Err(FrequenciesError::Empty);
+        }
+        Ok(values)
+    }
+    /// Checks the sampling parameters
+    ///
+    /// The intervals must be sampled with at least 2 frequencies,
+    /// and with positive frequencies for the logarithmic and adaptive samplings
+    pub fn validate(&self) -> Result<(), FrequenciesError> {
+        let interval = |lower: f64, upper: f64, n: usize| {
+            if lower >= upper {
+                Err(FrequenciesError::Interval { lower, upper })
+            } else if n < 2 {
+                Err(FrequenciesError::Samples(n))
+            } else {
+                Ok(())
+            }
+        };
+        match self {
+            Frequencies::LogSpace { lower, upper, n }
+            | Frequencies::Adaptive {
+                lower, upper, n, ..
+            } => {
+                interval(*lower, *upper, *n)?;
+                if *lower <= 0. {
+                    return Err(FrequenciesError::LogInterval {
+                        lower: *lower,
+                        upper: *upper,
+                    });
+                }
+                Ok(())
+            }
+            Frequencies::LinSpace { lower, upper, n } => interval(*lower, *upper, *n),
+            Frequencies::Set { values } if values.is_empty() => Err(FrequenciesError::Empty),
+            Frequencies::Compose { specs } if specs.is_empty() => Err(FrequenciesError::Empty),
+            Frequencies::Compose { specs } => specs.iter().try_for_each(Frequencies::validate),
+            _ => Ok(()),
+        }
+    }
+    /// Converts the frequencies given in `unit` to Hz
+    ///
+    /// The frequencies read from a file are converted into a [Frequencies::Set]
+    pub fn into_hz(self, unit: FrequencyUnit) -> Result<Self, FrequenciesError> {
+        if unit == FrequencyUnit::Hz {
+            return Ok(self);
+        }
+        let hz = |x: f64| unit.to_hz(x);
+        Ok(match self {
+            Frequencies::Single { value } => Frequencies::Single { value: hz(value) },
+            Frequencies::LogSpace { lower, upper, n } => Frequencies::LogSpace {
+                lower: hz(lower),
+                upper: hz(upper),
+                n,
+            },
+            Frequencies::LinSpace { lower, upper, n } => Frequencies::LinSpace {
+                lower: hz(lower),
+                upper: hz(upper),
+                n,
+            },
+            Frequencies::Set { values } => Frequencies::Set {
+                values: values.into_iter().map(hz).collect(),
+            },
+            Frequencies::Adaptive {
+                lower,
+                upper,
+                n,
+                magnitude_tolerance,
+                phase_tolerance,
+                max_n,
+            } => Frequencies::Adaptive {
+                lower: hz(lower),
+                upper: hz(upper),
+                n,
+                magnitude_tolerance,
+                phase_tolerance,
+                max_n,
+            },
+            file @ Frequencies::File { .. } => Frequencies::Set {
+                values: file.values()?.into_iter().map(hz).collect(),
+            },
+            Frequencies::Compose { specs } => Frequencies::Compose {
+                specs: specs
+                    .into_iter()
+                    .map(|spec| spec.into_hz(unit))
+                    .collect::<Result<_, _>>()?,
+            },
+        })
+    }
     pub fn logspace(lower: f64, upper: f64, n: usize) -> Self {
         Self::LogSpace { lower, upper, n }
     }
     pub fn linspace(lower: f64, upper: f64, n: usize) -> Self {
         Self::LinSpace { lower, upper, n }
     }
+    /// Adaptive sampling with the default tolerances: 0.1dB and 1deg, and at most 10000 frequencies
+    pub fn adaptive(lower: f64, upper: f64, n: usize) -> Self {
+        Self::Adaptive {
+            lower,
+            upper,
+            n,
+            magnitude_tolerance: 0.1,
+            phase_tolerance: 1.,
+            max_n: 10_000,
+        }
+    }
+}
+
+/// Reads the frequencies from either a text table, a numpy or a Matlab file
+fn read_frequencies(path: impl AsRef<Path>, variable: &str) -> Result<Vec<f64>, FrequenciesError> {
+    let path = path.as_ref();
+    Ok(match path.extension() {
+        Some(ext) if ext == "npy" => {
+            npyz::NpyFile::new(BufReader::new(File::open(path)?))?.into_vec::<f64>()?
+        }
+        Some(ext) if ext == "mat" => matio_rs::MatFile::load(path)?.var(variable)?,
+        _ => table::read_values(path)?,
+    })
+}
+
+/// Shard `k/N` of a frequency grid
+///
+/// The grid is split into `N` contiguous blocks of nearly equal sizes
+/// and the shard is the `k`th block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shard {
+    /// shard index (1-based)
+    pub k: usize,
+    /// number of shards
+    pub n: usize,
+}
+impl FromStr for Shard {
+    type Err = FrequenciesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let shard = s
+            .split_once('/')
+            .and_then(|(k, n)| Some((k.trim().parse().ok()?, n.trim().parse().ok()?)))
+            .map(|(k, n)| Self { k, n })
+            .filter(|shard| shard.k >= 1 && shard.k <= shard.n)
+            .ok_or_else(|| FrequenciesError::Shard(s.to_string()))?;
+        Ok(shard)
+    }
+}
+impl Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.k, self.n)
+    }
+}
+impl Shard {
+    /// Returns the frequencies of the shard
+    pub fn select(&self, frequencies: &[f64]) -> Vec<f64> {
+        let len = frequencies.len();
+        frequencies[(self.k - 1) * len / self.n..self.k * len / self.n].to_vec()
+    }
 }
 
 /// Frequency response interface definition
@@ -90,58 +427,131 @@ pub trait FrequencyResponse {
     /// Returns the frequencies and the frequency response
     ///
     /// The argument is frequencies in Hz
+    ///
+    /// # Panics
+    /// If the frequencies cannot be evaluated, see [FrequencyResponse::try_frequency_response]
+    #[deprecated(note = "use `try_frequency_response` that returns an error instead of panicking")]
     fn frequency_response<T: Into<Frequencies>>(&self, nu: T) -> FrequencyResponseVec<Self::Output>
+    where
+        <Self as FrequencyResponse>::Output: Cartesian2Polar + Send,
+        <<Self as FrequencyResponse>::Output as Cartesian2Polar>::Output: Send,
+        Self: Sync,
+    {
+        self.try_frequency_response(nu)
+            .expect("failed to evaluate the frequencies")
+    }
+    /// Returns the frequencies and the frequency response or an error if the frequencies cannot be evaluated
+    ///
+    /// The argument is frequencies in Hz
+    fn try_frequency_response<T: Into<Frequencies>>(
+        &self,
+        nu: T,
+    ) -> Result<FrequencyResponseVec<Self::Output>, FrequenciesError>
     where
         <Self as FrequencyResponse>::Output: Cartesian2Polar + Send,
         <<Self as FrequencyResponse>::Output as Cartesian2Polar>::Output: Send,
         Self: Sync,
     {
         let frequencies: Frequencies = nu.into();
-        let style = ProgressStyle::with_template("|{bar} {pos}|")
-            .unwrap()
-            .progress_chars("-.-");
-        let data = match frequencies {
-            Frequencies::Single { value: nu } => {
-                let jw = Complex::new(0f64, DPI * nu);
-                vec![FrequencyResponseData::new(nu, self.j_omega(jw))]
+        Ok(match frequencies {
+            Frequencies::Adaptive {
+                lower,
+                upper,
+                n,
+                magnitude_tolerance,
+                phase_tolerance,
+                max_n,
+            } => {
+                let mut nu = Frequencies::logspace(lower, upper, n).values()?;
+                nu.extend(
+                    self.resonance_frequencies()
+                        .into_iter()
+                        .filter(|f| *f > lower && *f < upper),
+                );
+                FrequencyResponseVec::new(self.adaptive_frequency_responses(
+                    nu,
+                    magnitude_tolerance,
+                    phase_tolerance.to_radians(),
+                    max_n,
+                ))
             }
-            Frequencies::LogSpace { lower, upper, n } => {
-                assert!(upper > lower);
-                let log_step = (upper.log10() - lower.log10()) / (n - 1) as f64;
-                (0..n)
-                    .into_par_iter()
-                    .progress_with_style(style)
-                    .map(|i| {
-                        let log_nu = lower.log10() + log_step * i as f64;
-                        let nu = 10f64.powf(log_nu);
-                        let jw = Complex::new(0f64, DPI * nu);
-                        FrequencyResponseData::new(nu, self.j_omega(jw))
-                    })
-                    .collect()
-            }
-            Frequencies::LinSpace { lower, upper, n } => {
-                assert!(upper > lower);
-                let step = (upper - lower) / (n - 1) as f64;
-                (0..n)
-                    .into_par_iter()
-                    .progress_with_style(style)
-                    .map(|i| {
-                        let nu = lower + step * i as f64;
-                        let jw = Complex::new(0f64, DPI * nu);
-                        FrequencyResponseData::new(nu, self.j_omega(jw))
-                    })
-                    .collect()
+            _ => FrequencyResponseVec::new(self.frequency_responses(frequencies.values()?)),
+        })
+    }
+    /// Returns the resonance frequencies \[Hz\] the adaptive sampling is seeded with
+    fn resonance_frequencies(&self) -> Vec<f64> {
+        Vec::new()
+    }
+    /// Returns the frequency responses at the seed frequencies \[Hz\] and at the frequencies
+    /// added at the middle (in log scale) of the intervals where the magnitude \[dB\]
+    /// or the phase \[rd\] interpolation errors are larger than the tolerances
+    ///
+    /// Each refinement step evaluates the frequency responses of all the new frequencies in parallel,
+    /// the refinement stops when all the errors are less than the tolerances or when there are `max_n` frequencies
+    fn adaptive_frequency_responses(
+        &self,
+        mut nu: Vec<f64>,
+        magnitude_tolerance: f64,
+        phase_tolerance: f64,
+        max_n: usize,
+    ) -> Vec<FrequencyResponseData<Self::Output>>
+    where
+        <Self as FrequencyResponse>::Output: Cartesian2Polar + Send,
+        <<Self as FrequencyResponse>::Output as Cartesian2Polar>::Output: Send,
+        Self: Sync,
+    {
+        nu.retain(|nu| *nu > 0.);
+        nu.sort_by(f64::total_cmp);
+        nu.dedup();
+        // positive frequencies are ordered as their bits
+        let mut data: BTreeMap<u64, FrequencyResponseData<Self::Output>> = self
+            .frequency_responses(nu.clone())
+            .into_iter()
+            .map(|data| (data.frequency().to_bits(), data))
+            .collect();
+        let mut intervals: Vec<(f64, f64)> = nu.windows(2).map(|w| (w[0], w[1])).collect();
+        while !intervals.is_empty() && data.len() < max_n {
+            intervals.retain(|(a, b)| b / a - 1. > f64::EPSILON.sqrt());
+            intervals.truncate(max_n - data.len());
+            let mid: Vec<f64> = intervals.iter().map(|(a, b)| (a * b).sqrt()).collect();
+            let mid_data = self.frequency_responses(mid);
+            let mut refined = vec![];
+            for ((a, b), m) in intervals.into_iter().zip(mid_data) {
+                let refine = FrequencyResponseData::midpoint_error(
+                    &data[&a.to_bits()],
+                    &m,
+                    &data[&b.to_bits()],
+                )
+                .is_some_and(|(magnitude_error, phase_error)| {
+                    magnitude_error > magnitude_tolerance || phase_error > phase_tolerance
+                });
+                let nu_m = m.frequency();
+                if refine {
+                    refined.push((a, nu_m));
+                    refined.push((nu_m, b));
+                }
+                data.insert(nu_m.to_bits(), m);
             }
-            Frequencies::Set { values: nu } => nu
-                .into_par_iter()
-                .progress_with_style(style)
-                .map(|nu| {
-                    let jw = Complex::new(0f64, DPI * nu);
-                    FrequencyResponseData::new(nu, self.j_omega(jw))
-                })
-                .collect(),
-        };
-        FrequencyResponseVec::new(data)
+            intervals = refined;
+        }
+        data.into_values().collect()
+    }
+    /// Returns the frequency response at each frequency in Hz
+    ///
+    /// The frequency responses are computed in parallel
+    fn frequency_responses(&self, nu: Vec<f64>) -> Vec<FrequencyResponseData<Self::Output>>
+    where
+        <Self as FrequencyResponse>::Output: Cartesian2Polar + Send,
+        <<Self as FrequencyResponse>::Output as Cartesian2Polar>::Output: Send,
+        Self: Sync,
+    {
+        nu.into_par_iter()
+            .progress_with_style(progress_style())
+            .map(|nu| {
+                let jw = Complex::new(0f64, DPI * nu);
+                FrequencyResponseData::new(nu, self.j_omega(jw))
+            })
+            .collect()
     }
     /// Returns the first derivation of the frequency response
     fn j_omega_first(
@@ -241,14 +651,109 @@ impl FrequencyResponse for PICompensator {
 #[cfg(test)]
 mod tests {
     // use std::fs::File;
+    use crate::test_utils::TempFile;
 
     use super::*;
 
+    #[test]
+    fn shards() {
+        let nu = Frequencies::linspace(1., 10., 10).values().unwrap();
+        let shards: Vec<Shard> = ["1/3", "2/3", "3/3"]
+            .into_iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let merged: Vec<_> = shards.iter().flat_map(|shard| shard.select(&nu)).collect();
+        assert_eq!(merged, nu);
+        assert!("0/3".parse::<Shard>().is_err());
+        assert!("4/3".parse::<Shard>().is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let path = TempFile::new("frequencies.csv");
+        std::fs::write(&path, "# crossovers [Hz]\n7.5\n20\n").unwrap();
+        let specs = [
+            "log-space:1:100:3".to_string(),
+            "set:20, 5".to_string(),
+            "2".to_string(),
+            format!("file:{}", path.as_ref().display()),
+        ];
+        let nu = Frequencies::Compose {
+            specs: specs.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+        .values()
+        .unwrap();
+        assert_eq!(nu, vec![1., 2., 5., 7.5, 10., 20., 100.]);
+        assert!("log-space:1:100".parse::<Frequencies>().is_err());
+        assert!("grid:1".parse::<Frequencies>().is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Frequencies::logspace(10., 1., 100).values(),
+            Err(FrequenciesError::Interval { .. })
+        ));
+        assert!(matches!(
+            Frequencies::linspace(1., 10., 1).values(),
+            Err(FrequenciesError::Samples(1))
+        ));
+        assert!(matches!(
+            Frequencies::logspace(0., 10., 100).values(),
+            Err(FrequenciesError::LogInterval { .. })
+        ));
+        assert!(matches!(
+            Frequencies::from(Vec::<f64>::new()).values(),
+            Err(FrequenciesError::Empty)
+        ));
+    }
+
+    #[test]
+    fn rad_per_second() {
+        let unit = FrequencyUnit::RadPerSecond;
+        let nu = Frequencies::Compose {
+            specs: vec![DPI.into(), Frequencies::linspace(2. * DPI, 4. * DPI, 3)],
+        }
+        .into_hz(unit)
+        .unwrap()
+        .values()
+        .unwrap();
+        assert_eq!(nu, vec![1., 2., 3., 4.]);
+        assert!((unit.hz_to(1.) - DPI).abs() < 1e-12);
+    }
+
+    #[test]
+    fn adaptive() {
+        struct Resonance;
+        impl FrequencyResponse for Resonance {
+            type Output = if64;
+            fn j_omega(&self, jw: if64) -> Self::Output {
+                let w0 = DPI * 10.;
+                1. / (w0 * w0 + 2. * 0.005 * w0 * jw + jw * jw)
+            }
+            fn resonance_frequencies(&self) -> Vec<f64> {
+                vec![10.]
+            }
+        }
+        let tf = Resonance
+            .try_frequency_response(Frequencies::adaptive(1., 100., 11))
+            .unwrap();
+        let nu = tf.frequencies();
+        assert!(nu.windows(2).all(|w| w[0] < w[1]));
+        assert!(nu.contains(&10.));
+        // the sampling is refined around the resonance
+        let near = nu.iter().filter(|f| (**f - 10.).abs() < 0.5).count();
+        assert!(near > 10);
+        assert!(nu.len() < 10_000);
+    }
+
     #[test]
     fn folp_tf() {
         let folp = FirstOrderLowPass::new();
 
-        let tf = folp.frequency_response(Frequencies::logspace(1., 8e3, 1000));
+        let tf = folp
+            .try_frequency_response(Frequencies::logspace(1., 8e3, 1000))
+            .unwrap();
 
         // let mut file = File::create("folp_tf.pkl").unwrap();
         // serde_pickle::to_writer(&mut file, &(nu, tf), Default::default()).unwrap();
@@ -258,7 +763,9 @@ mod tests {
     fn bessel_tf() {
         let bessel = BesselFilter::new();
 
-        let tf = bessel.frequency_response(Frequencies::logspace(1., 8e3, 1000));
+        let tf = bessel
+            .try_frequency_response(Frequencies::logspace(1., 8e3, 1000))
+            .unwrap();
 
         // let mut file = File::create("bessel_tf.pkl").unwrap();
         // serde_pickle::to_writer(&mut file, &(nu, tf), Default::default()).unwrap();
@@ -268,7 +775,9 @@ mod tests {
     fn pic_tf() {
         let pic = PICompensator::new();
 
-        let tf = pic.frequency_response(Frequencies::logspace(1., 8e3, 1000));
+        let tf = pic
+            .try_frequency_response(Frequencies::logspace(1., 8e3, 1000))
+            .unwrap();
 
         // let mut file = File::create("pic_tf.pkl").unwrap();
         // serde_pickle::to_writer(&mut file, &(nu, tf), Default::default()).unwrap();
@@ -0,0 +1,170 @@
+//! FEM inventory
+//!
+//! Lists the FEM inputs and outputs with their number of channels
+//! and summarizes the FEM eigen frequencies
+
+use std::{fmt::Display, io};
+
+use gmt_fem::FEM;
+use serde::Serialize;
+
+use crate::discovery::{FemIo, FemIoNames};
+
+#[derive(Debug, thiserror::Error)]
+pub enum InventoryError {
+    #[error("failed to load the FEM")]
+    FEM(#[from] gmt_fem::FemError),
+    #[error("failed to serialize the FEM inventory to JSON")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to write the FEM inventory")]
+    IO(#[from] io::Error),
+    #[error("the histogram band edges must be increasing, found {0:?}")]
+    Bands(Vec<f64>),
+}
+type Result<T> = std::result::Result<T, InventoryError>;
+
+/// List command arguments
+#[derive(Debug, Clone, clap::Args)]
+pub struct ListArgs {
+    /// eigen frequencies histogram band edges \[Hz\], the last band is open-ended
+    #[arg(short, long, value_delimiter = ',', default_values_t = [0., 1., 10., 100., 1000.])]
+    pub bands: Vec<f64>,
+    /// writes the inventory in the JSON format
+    #[arg(long)]
+    pub json: bool,
+}
+impl ListArgs {
+    /// Loads the FEM from `FEM_REPO` and writes its inventory to the standard output
+    pub fn run(&self) -> Result<()> {
+        let inventory = FemInventory::new(&FEM::from_env()?, &self.bands)?;
+        if self.json {
+            serde_json::to_writer_pretty(io::stdout().lock(), &inventory)?;
+            println!();
+        } else {
+            println!("{inventory}");
+        }
+        Ok(())
+    }
+}
+
+/// Number of eigen frequencies within a band
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistogramBand {
+    /// band lower frequency (inclusive) \[Hz\]
+    pub lower: f64,
+    /// band upper frequency (exclusive) \[Hz\], `None` for the open-ended band
+    pub upper: Option<f64>,
+    pub count: usize,
+}
+
+/// Eigen frequencies summary
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EigenFrequencies {
+    pub n_mode: usize,
+    /// minimum eigen frequency \[Hz\]
+    pub min: Option<f64>,
+    /// maximum eigen frequency \[Hz\]
+    pub max: Option<f64>,
+    pub histogram: Vec<HistogramBand>,
+}
+impl EigenFrequencies {
+    /// Summarizes the eigen frequencies \[Hz\] with a histogram given by the band edges \[Hz\]
+    pub fn new(eigen_frequencies: &[f64], edges: &[f64]) -> Result<Self> {
+        if edges.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(InventoryError::Bands(edges.to_vec()));
+        }
+        let histogram = edges
+            .iter()
+            .enumerate()
+            .map(|(i, &lower)| {
+                let upper = edges.get(i + 1).copied();
+                HistogramBand {
+                    lower,
+                    upper,
+                    count: eigen_frequencies
+                        .iter()
+                        .filter(|&&f| f >= lower && upper.is_none_or(|upper| f < upper))
+                        .count(),
+                }
+            })
+            .collect();
+        Ok(Self {
+            n_mode: eigen_frequencies.len(),
+            min: eigen_frequencies.iter().copied().reduce(f64::min),
+            max: eigen_frequencies.iter().copied().reduce(f64::max),
+            histogram,
+        })
+    }
+}
+
+/// FEM inputs, outputs and eigen frequencies
+#[derive(Debug, Clone, Serialize)]
+pub struct FemInventory {
+    pub inputs: Vec<FemIo>,
+    pub outputs: Vec<FemIo>,
+    pub eigen_frequencies: EigenFrequencies,
+}
+impl FemInventory {
+    /// Creates the inventory of the FEM with the eigen frequencies histogram band edges \[Hz\]
+    pub fn new(fem: &FEM, edges: &[f64]) -> Result<Self> {
+        let FemIoNames { inputs, outputs } = FemIoNames::from(fem);
+        Ok(Self {
+            inputs,
+            outputs,
+            eigen_frequencies: EigenFrequencies::new(&fem.eigen_frequencies, edges)?,
+        })
+    }
+}
+
+impl Display for FemInventory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "FEM inputs:")?;
+        for io in &self.inputs {
+            writeln!(f, " {:>32}: [{:5}]", io.name, io.size)?;
+        }
+        writeln!(f, "FEM outputs:")?;
+        for io in &self.outputs {
+            writeln!(f, " {:>32}: [{:5}]", io.name, io.size)?;
+        }
+        let EigenFrequencies {
+            n_mode,
+            min,
+            max,
+            histogram,
+        } = &self.eigen_frequencies;
+        write!(f, "FEM eigen frequencies: {n_mode} modes")?;
+        if let (Some(min), Some(max)) = (min, max) {
+            write!(f, " in ({min:.3},{max:.3})Hz")?;
+        }
+        writeln!(f)?;
+        for band in histogram {
+            match band.upper {
+                Some(upper) => write!(f, " [{:>9.3},{:>9.3}[Hz", band.lower, upper)?,
+                None => write!(f, " [{:>9.3},      inf[Hz", band.lower)?,
+            }
+            writeln!(f, ": {:6}", band.count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram() {
+        let eigen =
+            EigenFrequencies::new(&[0., 0.5, 2., 20., 30., 2000.], &[0., 1., 10., 100.]).unwrap();
+        assert_eq!(eigen.n_mode, 6);
+        assert_eq!(eigen.max, Some(2000.));
+        let counts: Vec<_> = eigen.histogram.iter().map(|band| band.count).collect();
+        assert_eq!(counts, vec![2, 1, 2, 1]);
+        assert_eq!(eigen.histogram[3].upper, None);
+    }
+
+    #[test]
+    fn decreasing_bands() {
+        assert!(EigenFrequencies::new(&[1.], &[10., 1.]).is_err());
+    }
+}
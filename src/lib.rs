@@ -1,13 +1,29 @@
 //! GMT FEM frequency response
 
+use std::path::PathBuf;
+
 use clap::ValueEnum;
 
 pub mod cli;
 #[doc(inline)]
 pub use cli::Cli;
+pub mod compare;
+pub mod damping;
 pub mod data;
+pub mod discovery;
 pub mod frequency_response;
+pub mod inventory;
+pub mod merge;
+pub mod modal_model;
+pub mod selection;
+pub mod stream;
 pub mod structural;
+mod table;
+#[cfg(feature = "tabular")]
+mod tabular;
+#[cfg(test)]
+mod test_utils;
+mod uff;
 
 include!(concat!(env!("OUT_DIR"), "/fem_io.rs"));
 
@@ -17,25 +33,38 @@ pub enum Error {
     Cli(#[from] cli::CliError),
     #[error("failed to build structural model")]
     Structural(#[from] structural::StructuralError),
+    #[error("invalid sampling frequencies")]
+    Frequencies(#[from] frequency_response::FrequenciesError),
 }
 
 impl TryFrom<&Cli> for structural::Structural {
     type Error = crate::Error;
 
     fn try_from(args: &Cli) -> Result<Self, Self::Error> {
-        Ok(structural::StructuralBuilder {
+        let builder = structural::StructuralBuilder {
             built: structural::Structural {
                 inputs: args.fem_inputs(),
                 outputs: args.fem_outputs(),
-                z: args.structural_damping,
+                damping_model: args.damping_model,
+                damping: args.damping_schedule()?,
                 optical_senses: args.lom_sensitivies()?,
                 ..Default::default()
             },
             min_eigen_frequency: args.eigen_frequency_min,
             max_eigen_frequency: args.eigen_frequency_max,
+            file_name: "structural".into(),
+            cache: args.cache_policy(),
+            residual_correction: args.residual_correction,
+            modal_model: args.modal_model.as_ref().map(PathBuf::from),
+            inputs_channels: args.inputs_channels(),
+            outputs_channels: args.outputs_channels(),
             ..Default::default()
+        };
+        Ok(if args.static_gain_mismatch_compensation {
+            builder.enable_static_gain_mismatch_compensation(args.static_gain_mismatch_delay)
+        } else {
+            builder
         }
         .build()?)
     }
 }
-
@@ -1,28 +1,72 @@
-use std::time::Instant;
+use std::{env, fs, time::Instant};
 
 use clap::Parser;
 use gmt_fem_frequency_response::{
-    Cli, data::TransferFunctionData, frequency_response::FrequencyResponse, structural::Structural,
+    Cli,
+    cli::Command,
+    data::{FrequencyResponseVec, TransferFunctionData},
+    frequency_response::FrequencyResponse,
+    stream::{self, StreamWriter},
+    structural::Structural,
 };
 
 fn main() -> anyhow::Result<()> {
     let args: Cli = Cli::parse();
+    if let Some(fem) = &args.fem {
+        // SAFETY: the environment is set before any other thread is spawned
+        unsafe { env::set_var("FEM_REPO", fem) };
+    }
+    let frequencies = match &args.command {
+        Command::List(list) => return Ok(list.run()?),
+        Command::Merge(merge) => return Ok(merge.run(&args.filename)?),
+        Command::Compare(compare) => return Ok(compare.run()?),
+        Command::Frequencies(frequencies) => frequencies.clone(),
+    };
+    let args = args.validate()?;
 
     let model = Structural::try_from(&args)?;
     println!("{model}");
 
     let now = Instant::now();
-    let frequency_response = model.frequency_response(&args.frequencies);
+    let data = TransferFunctionData::from(&args)
+        .add_structural(&model)
+        .shard(args.shard);
+    let frequencies = args.shard_frequencies(&frequencies)?;
+    if args.stream {
+        StreamWriter::create(&args.filename, &data)?.stream(
+            &model,
+            &frequencies.values()?,
+            args.chunk_size,
+        )?;
+        println!(
+            "frequency response computed and streamed to {} in {:.3}s",
+            args.filename,
+            now.elapsed().as_secs_f64()
+        );
+        return Ok(());
+    }
+    let frequency_response = if args.checkpoint || args.resume {
+        FrequencyResponseVec::new(stream::checkpointed(
+            &model,
+            &data,
+            &frequencies.values()?,
+            args.chunk_size,
+            args.checkpoint_path(),
+            args.resume,
+        )?)
+    } else {
+        model.try_frequency_response(frequencies)?
+    };
     println!(
         "frequency response computed in {:.3}s",
         now.elapsed().as_secs_f64()
     );
     println!("{frequency_response}");
 
-    TransferFunctionData::from(&args)
-        .add_structural(&model)
-        .add_response(frequency_response)
-        .dump(args.filename)?;
+    data.add_response(frequency_response).dump(&args.filename)?;
+    if args.checkpoint || args.resume {
+        fs::remove_file(args.checkpoint_path())?;
+    }
 
     Ok(())
 }
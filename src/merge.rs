@@ -0,0 +1,40 @@
+//! Frequency sweep shards merging
+
+use std::path::Path;
+
+use crate::data::{TransferFunctionData, TransferFunctionDataError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error("failed to read shard {0}")]
+    Read(String, #[source] TransferFunctionDataError),
+    #[error("failed to merge shards")]
+    Merge(#[from] TransferFunctionDataError),
+    #[error("failed to write merged shards")]
+    Write(#[source] TransferFunctionDataError),
+}
+type Result<T> = std::result::Result<T, MergeError>;
+
+/// Merge command arguments
+#[derive(Debug, Clone, clap::Args)]
+pub struct MergeArgs {
+    /// shards data files, either pickle, streamed pickle or Matlab files
+    #[arg(required = true)]
+    pub shards: Vec<String>,
+}
+impl MergeArgs {
+    /// Merges the shards and writes the merged data to `path`
+    pub fn run(&self, path: impl AsRef<Path>) -> Result<()> {
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| {
+                TransferFunctionData::load(shard).map_err(|e| MergeError::Read(shard.clone(), e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        TransferFunctionData::merge(shards)?
+            .dump(path)
+            .map_err(MergeError::Write)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,506 @@
+//! Modal model
+//!
+//! A modal model is the set of matrices a [Structural](crate::structural::Structural) model is built from.
+//! It is either derived from the FEM or loaded from a file saved by another tool.
+//!
+//! A modal model file is either a Matlab (.mat), a NumPy (.npz) or a Python pickle (.pkl) file with the variables:
+//!  * `inputs`: the inputs labels,
+//!  * `inputs_size`: the number of channels of each input,
+//!  * `outputs`: the outputs labels,
+//!  * `outputs_size`: the number of channels of each output,
+//!  * `eigen_frequencies`: the modes eigen frequencies \[Hz\],
+//!  * `b`: the inputs to modal forces matrix `[n_modes,n_inputs]`,
+//!  * `c`: the modal displacements to outputs matrix `[n_outputs,n_modes]`,
+//!  * `static_gain` (optional): the static gain matrix `[n_outputs,n_inputs]`.
+//!
+//! In a pickle file, the variables are the keys of a dictionary and the matrices are lists of rows.
+//!
+//! The rigid body modes, excluded from the modal static gain, are the modes with an eigen frequency
+//! less than 1e-3rd/s for a modal model file and the first 3 modes for the FEM.
+
+use std::{collections::BTreeMap, fs::File, io, io::BufReader, ops::Range, path::Path};
+
+use nalgebra::DMatrix;
+use serde::Deserialize;
+
+use crate::selection::Channels;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModalModelError {
+    #[error("failed to open modal model file")]
+    IO(#[from] io::Error),
+    #[error("failed to read modal model from Matlab file")]
+    Matlab(#[from] matio_rs::MatioError),
+    #[error("failed to read modal model from pickle file")]
+    Pickle(#[from] serde_pickle::Error),
+    #[error("failed to read {0} from NumPy file")]
+    Npz(String, #[source] io::Error),
+    #[error("{0} is missing from modal model file")]
+    Missing(String),
+    #[error(r#"found modal model file extension: "{0}", expected "mat", "npz" or "pkl""#)]
+    Extension(String),
+    #[error("modal model {0} dimensions mismatch: {1}")]
+    Shape(String, String),
+    #[error("{0} not found in modal model, expected one of {1:?}")]
+    Label(String, Vec<String>),
+    #[error("channel #{1} of {0} is out of range, {0} has {2} channels")]
+    Channel(String, usize, usize),
+}
+type Result<T> = std::result::Result<T, ModalModelError>;
+
+/// Eigen frequencies \[rd/s\] below this threshold are rigid body modes,
+/// unless the number of rigid body modes is given
+///
+/// Rigid body modes are excluded from the modal static gain
+const RIGID_BODY_EIGEN_FREQUENCY: f64 = 1e-3;
+
+/// Modal model
+#[derive(Debug, Default, Clone)]
+pub struct ModalModel {
+    /// model name
+    pub name: String,
+    /// inputs labels
+    pub inputs: Vec<String>,
+    /// number of channels of each input
+    pub inputs_size: Vec<usize>,
+    /// outputs labels
+    pub outputs: Vec<String>,
+    /// number of channels of each output
+    pub outputs_size: Vec<usize>,
+    /// eigen frequencies \[Hz\]
+    pub eigen_frequencies: Vec<f64>,
+    /// inputs to modal forces matrix `[n_modes,n_inputs]`
+    pub b: DMatrix<f64>,
+    /// modal displacements to outputs matrix `[n_outputs,n_modes]`
+    pub c: DMatrix<f64>,
+    /// static gain matrix `[n_outputs,n_inputs]`
+    pub static_gain: Option<DMatrix<f64>>,
+    /// number of leading rigid body modes, if `None` the rigid body modes
+    /// are the modes with an eigen frequency less than 1e-3rd/s
+    pub rigid_body_modes: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct PickleModalModel {
+    inputs: Vec<String>,
+    inputs_size: Vec<usize>,
+    outputs: Vec<String>,
+    outputs_size: Vec<usize>,
+    eigen_frequencies: Vec<f64>,
+    b: Vec<Vec<f64>>,
+    c: Vec<Vec<f64>>,
+    #[serde(default)]
+    static_gain: Option<Vec<Vec<f64>>>,
+}
+
+fn from_rows(name: &str, rows: Vec<Vec<f64>>) -> Result<DMatrix<f64>> {
+    let ncols = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != ncols) {
+        return Err(ModalModelError::Shape(
+            name.into(),
+            "rows have different lengths".into(),
+        ));
+    }
+    Ok(DMatrix::from_row_iterator(
+        rows.len(),
+        ncols,
+        rows.into_iter().flatten(),
+    ))
+}
+
+// Reads the shape, the memory order and the data of an array in a NumPy archive
+fn npz_read<R, T>(
+    npz: &mut npyz::npz::NpzArchive<R>,
+    name: &str,
+) -> Result<(Vec<usize>, npyz::Order, Vec<T>)>
+where
+    R: io::Read + io::Seek,
+    T: npyz::Deserialize,
+{
+    let npy = npz
+        .by_name(name)
+        .map_err(|e| ModalModelError::Npz(name.into(), e))?
+        .ok_or_else(|| ModalModelError::Missing(name.into()))?;
+    let shape = npy.shape().iter().map(|&n| n as usize).collect();
+    let order = npy.order();
+    let data = npy
+        .into_vec::<T>()
+        .map_err(|e| ModalModelError::Npz(name.into(), e))?;
+    Ok((shape, order, data))
+}
+
+// Reads a 2D array of a NumPy archive into a matrix
+fn npz_matrix<R: io::Read + io::Seek>(
+    npz: &mut npyz::npz::NpzArchive<R>,
+    name: &str,
+) -> Result<DMatrix<f64>> {
+    let (shape, order, data) = npz_read::<_, f64>(npz, name)?;
+    match (shape.as_slice(), order) {
+        (&[n, m], npyz::Order::C) => Ok(DMatrix::from_row_slice(n, m, &data)),
+        (&[n, m], npyz::Order::Fortran) => Ok(DMatrix::from_column_slice(n, m, &data)),
+        _ => Err(ModalModelError::Shape(
+            name.into(),
+            format!("expected a 2D array, found shape {shape:?}"),
+        )),
+    }
+}
+
+impl ModalModel {
+    /// Loads a modal model from a Matlab (.mat), NumPy (.npz) or pickle (.pkl) file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut this = match path.extension() {
+            Some(ext) if ext == "mat" => Self::load_mat(path)?,
+            Some(ext) if ext == "npz" => Self::load_npz(path)?,
+            Some(ext) if ext == "pkl" => Self::load_pkl(path)?,
+            Some(ext) => {
+                return Err(ModalModelError::Extension(
+                    ext.to_string_lossy().into_owned(),
+                ));
+            }
+            None => return Err(ModalModelError::Extension(String::new())),
+        };
+        this.name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        this.check()?;
+        Ok(this)
+    }
+    fn load_mat(path: &Path) -> Result<Self> {
+        let mat_file = matio_rs::MatFile::load(path)?;
+        let sizes = |name: &str| -> Result<Vec<usize>> {
+            Ok(mat_file
+                .var::<_, Vec<f64>>(name)?
+                .into_iter()
+                .map(|x| x as usize)
+                .collect())
+        };
+        Ok(Self {
+            inputs: mat_file.var("inputs")?,
+            inputs_size: sizes("inputs_size")?,
+            outputs: mat_file.var("outputs")?,
+            outputs_size: sizes("outputs_size")?,
+            eigen_frequencies: mat_file.var("eigen_frequencies")?,
+            b: mat_file.var("b")?,
+            c: mat_file.var("c")?,
+            static_gain: mat_file.var("static_gain").ok(),
+            ..Default::default()
+        })
+    }
+    fn load_npz(path: &Path) -> Result<Self> {
+        let mut npz = npyz::npz::NpzArchive::open(path)?;
+        let sizes = |x: Vec<i64>| -> Vec<usize> { x.into_iter().map(|x| x as usize).collect() };
+        let (_, _, inputs) = npz_read::<_, String>(&mut npz, "inputs")?;
+        let (_, _, inputs_size) = npz_read::<_, i64>(&mut npz, "inputs_size")?;
+        let (_, _, outputs) = npz_read::<_, String>(&mut npz, "outputs")?;
+        let (_, _, outputs_size) = npz_read::<_, i64>(&mut npz, "outputs_size")?;
+        let (_, _, eigen_frequencies) = npz_read::<_, f64>(&mut npz, "eigen_frequencies")?;
+        let static_gain = match npz_matrix(&mut npz, "static_gain") {
+            Ok(static_gain) => Some(static_gain),
+            Err(ModalModelError::Missing(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            inputs,
+            inputs_size: sizes(inputs_size),
+            outputs,
+            outputs_size: sizes(outputs_size),
+            eigen_frequencies,
+            b: npz_matrix(&mut npz, "b")?,
+            c: npz_matrix(&mut npz, "c")?,
+            static_gain,
+            ..Default::default()
+        })
+    }
+    fn load_pkl(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let model: PickleModalModel =
+            serde_pickle::from_reader(BufReader::new(file), Default::default())?;
+        Ok(Self {
+            inputs: model.inputs,
+            inputs_size: model.inputs_size,
+            outputs: model.outputs,
+            outputs_size: model.outputs_size,
+            eigen_frequencies: model.eigen_frequencies,
+            b: from_rows("b", model.b)?,
+            c: from_rows("c", model.c)?,
+            static_gain: model
+                .static_gain
+                .map(|rows| from_rows("static_gain", rows))
+                .transpose()?,
+            ..Default::default()
+        })
+    }
+    /// Checks the consistency of the dimensions of the model
+    pub fn check(&self) -> Result<()> {
+        let n_modes = self.eigen_frequencies.len();
+        let n_inputs: usize = self.inputs_size.iter().sum();
+        let n_outputs: usize = self.outputs_size.iter().sum();
+        if self.inputs.len() != self.inputs_size.len() {
+            return Err(ModalModelError::Shape(
+                "inputs".into(),
+                format!(
+                    "{} labels for {} sizes",
+                    self.inputs.len(),
+                    self.inputs_size.len()
+                ),
+            ));
+        }
+        if self.outputs.len() != self.outputs_size.len() {
+            return Err(ModalModelError::Shape(
+                "outputs".into(),
+                format!(
+                    "{} labels for {} sizes",
+                    self.outputs.len(),
+                    self.outputs_size.len()
+                ),
+            ));
+        }
+        if self.b.shape() != (n_modes, n_inputs) {
+            return Err(ModalModelError::Shape(
+                "b".into(),
+                format!(
+                    "expected {:?}, found {:?}",
+                    (n_modes, n_inputs),
+                    self.b.shape()
+                ),
+            ));
+        }
+        if self.c.shape() != (n_outputs, n_modes) {
+            return Err(ModalModelError::Shape(
+                "c".into(),
+                format!(
+                    "expected {:?}, found {:?}",
+                    (n_outputs, n_modes),
+                    self.c.shape()
+                ),
+            ));
+        }
+        match &self.static_gain {
+            Some(g) if g.shape() != (n_outputs, n_inputs) => Err(ModalModelError::Shape(
+                "static_gain".into(),
+                format!(
+                    "expected {:?}, found {:?}",
+                    (n_outputs, n_inputs),
+                    g.shape()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+    /// Selects the inputs and outputs of the model, in the given order
+    ///
+    /// All the inputs or outputs are kept if the selection is empty
+    pub fn select(self, inputs: &[String], outputs: &[String]) -> Result<Self> {
+        let (inputs, inputs_size, in_idx) =
+            Self::channels(&self.inputs, &self.inputs_size, inputs)?;
+        let (outputs, outputs_size, out_idx) =
+            Self::channels(&self.outputs, &self.outputs_size, outputs)?;
+        Ok(Self {
+            inputs,
+            inputs_size,
+            outputs,
+            outputs_size,
+            b: self.b.select_columns(&in_idx),
+            c: self.c.select_rows(&out_idx),
+            static_gain: self
+                .static_gain
+                .map(|g| g.select_rows(&out_idx).select_columns(&in_idx)),
+            ..self
+        })
+    }
+    // Returns the selected labels, sizes and channels indices
+    fn channels(
+        labels: &[String],
+        sizes: &[usize],
+        selection: &[String],
+    ) -> Result<(Vec<String>, Vec<usize>, Vec<usize>)> {
+        let offsets: Vec<usize> = sizes
+            .iter()
+            .scan(0, |offset, size| {
+                let o = *offset;
+                *offset += size;
+                Some(o)
+            })
+            .collect();
+        let selection: Vec<usize> = if selection.is_empty() {
+            (0..labels.len()).collect()
+        } else {
+            selection
+                .iter()
+                .map(|name| {
+                    labels
+                        .iter()
+                        .position(|label| label == name)
+                        .ok_or_else(|| ModalModelError::Label(name.clone(), labels.to_vec()))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        Ok((
+            selection.iter().map(|&i| labels[i].clone()).collect(),
+            selection.iter().map(|&i| sizes[i]).collect(),
+            selection
+                .iter()
+                .flat_map(|&i| offsets[i]..offsets[i] + sizes[i])
+                .collect(),
+        ))
+    }
+    /// Selects the channels of the inputs and outputs of the model
+    ///
+    /// All the channels are kept for the inputs and outputs without a selection
+    pub fn select_channels(
+        self,
+        inputs: &BTreeMap<String, Channels>,
+        outputs: &BTreeMap<String, Channels>,
+    ) -> Result<Self> {
+        if inputs.is_empty() && outputs.is_empty() {
+            return Ok(self);
+        }
+        let (inputs_size, in_idx) = Self::channels_subset(&self.inputs, &self.inputs_size, inputs)?;
+        let (outputs_size, out_idx) =
+            Self::channels_subset(&self.outputs, &self.outputs_size, outputs)?;
+        Ok(Self {
+            inputs_size,
+            outputs_size,
+            b: self.b.select_columns(&in_idx),
+            c: self.c.select_rows(&out_idx),
+            static_gain: self
+                .static_gain
+                .map(|g| g.select_rows(&out_idx).select_columns(&in_idx)),
+            ..self
+        })
+    }
+    // Returns the sizes and channels indices of the channels selection
+    fn channels_subset(
+        labels: &[String],
+        sizes: &[usize],
+        selection: &BTreeMap<String, Channels>,
+    ) -> Result<(Vec<usize>, Vec<usize>)> {
+        if let Some(name) = selection.keys().find(|name| !labels.contains(name)) {
+            return Err(ModalModelError::Label(name.clone(), labels.to_vec()));
+        }
+        let mut offset = 0;
+        let mut new_sizes = vec![];
+        let mut idx = vec![];
+        for (label, &size) in labels.iter().zip(sizes) {
+            match selection.get(label) {
+                Some(channels) => {
+                    if let Some(&i) = channels.iter().find(|&&i| i >= size) {
+                        return Err(ModalModelError::Channel(label.clone(), i, size));
+                    }
+                    new_sizes.push(channels.len());
+                    idx.extend(channels.iter().map(|i| offset + i));
+                }
+                None => {
+                    new_sizes.push(size);
+                    idx.extend(offset..offset + size);
+                }
+            }
+            offset += size;
+        }
+        Ok((new_sizes, idx))
+    }
+    /// Keeps `n` modes starting from mode `s`
+    pub fn truncate(self, s: usize, n: usize) -> Self {
+        Self {
+            eigen_frequencies: self.eigen_frequencies[s..s + n].to_vec(),
+            b: self.b.rows(s, n).into_owned(),
+            c: self.c.columns(s, n).into_owned(),
+            rigid_body_modes: self.rigid_body_modes.map(|r| r.saturating_sub(s).min(n)),
+            ..self
+        }
+    }
+    /// Returns the static gain of the modes, excluding the rigid body modes
+    pub fn modal_static_gain(&self) -> DMatrix<f64> {
+        self.modes_static_gain(0..self.eigen_frequencies.len())
+    }
+    /// Returns the static gain of the `modes`, excluding the rigid body modes
+    pub fn modes_static_gain(&self, modes: Range<usize>) -> DMatrix<f64> {
+        let zeros = DMatrix::<f64>::zeros(self.c.nrows(), self.b.ncols());
+        self.c
+            .column_iter()
+            .zip(self.b.row_iter())
+            .zip(&self.eigen_frequencies)
+            .enumerate()
+            .map(|(k, (cb, f))| (k, cb, 2. * std::f64::consts::PI * f))
+            .filter(|(k, _, _)| modes.contains(k))
+            .filter(|(k, _, w)| match self.rigid_body_modes {
+                Some(n) => *k >= n,
+                None => *w > RIGID_BODY_EIGEN_FREQUENCY,
+            })
+            .fold(zeros, |a, (_, (c, b), w)| a + c * b / (w * w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> ModalModel {
+        ModalModel {
+            inputs: vec!["A".into(), "B".into()],
+            inputs_size: vec![1, 2],
+            outputs: vec!["X".into(), "Y".into()],
+            outputs_size: vec![2, 1],
+            eigen_frequencies: vec![0., 1.],
+            b: DMatrix::from_row_slice(2, 3, &[1., 2., 3., 4., 5., 6.]),
+            c: DMatrix::from_row_slice(3, 2, &[1., 2., 3., 4., 5., 6.]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn select() {
+        let model = model();
+        model.check().unwrap();
+        let selected = model
+            .select(&["B".to_string()], &["Y".to_string(), "X".to_string()])
+            .unwrap();
+        selected.check().unwrap();
+        assert_eq!(selected.b, DMatrix::from_row_slice(2, 2, &[2., 3., 5., 6.]));
+        assert_eq!(
+            selected.c,
+            DMatrix::from_row_slice(3, 2, &[5., 6., 1., 2., 3., 4.])
+        );
+    }
+
+    #[test]
+    fn select_channels() {
+        let inputs = BTreeMap::from([("B".to_string(), Channels::from(vec![1]))]);
+        let outputs = BTreeMap::from([("X".to_string(), Channels::from(vec![0]))]);
+        let selected = model().select_channels(&inputs, &outputs).unwrap();
+        selected.check().unwrap();
+        assert_eq!(selected.inputs_size, vec![1, 1]);
+        assert_eq!(selected.b, DMatrix::from_row_slice(2, 2, &[1., 3., 4., 6.]));
+        assert_eq!(selected.c, DMatrix::from_row_slice(2, 2, &[1., 2., 5., 6.]));
+        let outputs = BTreeMap::from([("Y".to_string(), Channels::from(vec![1]))]);
+        assert!(model().select_channels(&BTreeMap::new(), &outputs).is_err());
+    }
+
+    #[test]
+    fn rigid_body_modes() {
+        let gain = |model: &ModalModel, k: usize| {
+            let w = 2. * std::f64::consts::PI * model.eigen_frequencies[k];
+            model.c.column(k) * model.b.row(k) / (w * w)
+        };
+        // the rigid body modes of a modal model file are the modes with a null eigen frequency
+        let model = model();
+        assert!((model.modal_static_gain() - gain(&model, 1)).norm() < 1e-12);
+        // the rigid body modes of the FEM are the first modes whatever their eigen frequencies
+        let fem = ModalModel {
+            eigen_frequencies: vec![0.5, 1.],
+            rigid_body_modes: Some(1),
+            ..model
+        };
+        assert!((fem.modal_static_gain() - gain(&fem, 1)).norm() < 1e-12);
+        let fem = fem.truncate(1, 1);
+        assert_eq!(fem.rigid_body_modes, Some(0));
+        assert!((fem.modal_static_gain() - gain(&fem, 0)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn unknown_label() {
+        assert!(model().select(&["C".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! FEM inputs and outputs channels selection
+//!
+//! A subset of the channels of an input or an output is selected by appending
+//! a list of channel indices or ranges of indices to its name,
+//! e.g. `oss-harpoint-delta-f[0..6]` or `OSS_M1_lcl[0..6,42..48]`
+
+use std::{fmt::Display, num::ParseIntError, ops::Deref, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SelectionError {
+    #[error(r#"failed to parse channel "{0}""#)]
+    Parse(String, #[source] ParseIntError),
+    #[error(r#"invalid channels range "{0}""#)]
+    Range(String),
+    #[error(r#"missing closing bracket in "{0}""#)]
+    Bracket(String),
+    #[error("empty channels selection")]
+    Empty,
+}
+type Result<T> = std::result::Result<T, SelectionError>;
+
+/// Channels indices of a FEM input or output
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Channels(Vec<usize>);
+impl Deref for Channels {
+    type Target = [usize];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl From<Vec<usize>> for Channels {
+    fn from(value: Vec<usize>) -> Self {
+        Self(value)
+    }
+}
+impl From<std::ops::Range<usize>> for Channels {
+    fn from(value: std::ops::Range<usize>) -> Self {
+        Self(value.collect())
+    }
+}
+impl FromStr for Channels {
+    type Err = SelectionError;
+
+    /// Parses a comma separated list of indices `i`, ranges `i..j` or inclusive ranges `i..=j`
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |x: &str| {
+            x.trim()
+                .parse::<usize>()
+                .map_err(|e| SelectionError::Parse(x.to_string(), e))
+        };
+        let mut channels = vec![];
+        for item in s.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            match item.split_once("..") {
+                Some((start, end)) => {
+                    let start = parse(start)?;
+                    let end = match end.strip_prefix('=') {
+                        Some(end) => parse(end)? + 1,
+                        None => parse(end)?,
+                    };
+                    if start >= end {
+                        return Err(SelectionError::Range(item.to_string()));
+                    }
+                    channels.extend(start..end);
+                }
+                None => channels.push(parse(item)?),
+            }
+        }
+        if channels.is_empty() {
+            return Err(SelectionError::Empty);
+        }
+        Ok(Self(channels))
+    }
+}
+impl Display for Channels {
+    /// Writes the channels as a list of indices and ranges of consecutive indices
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut items = vec![];
+        let mut iter = self.0.iter().copied().peekable();
+        while let Some(start) = iter.next() {
+            let mut end = start;
+            while iter.peek() == Some(&(end + 1)) {
+                end += 1;
+                iter.next();
+            }
+            if end > start {
+                items.push(format!("{start}..{}", end + 1));
+            } else {
+                items.push(start.to_string());
+            }
+        }
+        write!(f, "{}", items.join(","))
+    }
+}
+
+/// FEM input or output with an optional channels selection
+#[derive(Debug, Clone, PartialEq)]
+pub struct IoSelection {
+    pub name: String,
+    pub channels: Option<Channels>,
+}
+impl FromStr for IoSelection {
+    type Err = SelectionError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('[') {
+            Some((name, channels)) => Ok(Self {
+                name: name.trim().to_string(),
+                channels: Some(
+                    channels
+                        .strip_suffix(']')
+                        .ok_or_else(|| SelectionError::Bracket(s.to_string()))?
+                        .parse()?,
+                ),
+            }),
+            None => Ok(Self {
+                name: s.trim().to_string(),
+                channels: None,
+            }),
+        }
+    }
+}
+impl Display for IoSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.channels {
+            Some(channels) => write!(f, "{}[{}]", self.name, channels),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let io: IoSelection = "oss-harpoint-delta-f[0..3, 5,7..=8]".parse().unwrap();
+        assert_eq!(io.name, "oss-harpoint-delta-f");
+        assert_eq!(
+            *io.channels.as_ref().unwrap(),
+            Channels(vec![0, 1, 2, 5, 7, 8])
+        );
+        assert_eq!(io.to_string(), "oss-harpoint-delta-f[0..3,5,7..9]");
+        let io: IoSelection = "OSS_M1_lcl".parse().unwrap();
+        assert!(io.channels.is_none());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("name[0..6".parse::<IoSelection>().is_err());
+        assert!("name[6..0]".parse::<IoSelection>().is_err());
+        assert!("name[]".parse::<IoSelection>().is_err());
+    }
+}
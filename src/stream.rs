@@ -0,0 +1,327 @@
+//! Frequency response streams
+//!
+//! A stream is a file made of consecutive Python pickles:
+//! the first pickle is the [TransferFunctionData] without the frequency response
+//! and each following pickle is a list of [FrequencyResponseData],
+//! written as soon as the frequency responses are computed.
+//! The frequency responses are written with the frequency unit, the representation
+//! and the magnitude and phase conventions of the [TransferFunctionData],
+//! the phase is unwrapped along the frequencies in the order they are written,
+//! the frequencies being sorted before they are streamed if the phase is unwrapped.
+//!
+//! The stream is read in Python with
+//! ```python
+//! import pickle
+//! with open("gmt_frequency_response.pkl", "rb") as f:
+//!     tf = pickle.load(f)
+//!     while True:
+//!         try:
+//!             tf["frequency_response"] += pickle.load(f)
+//!         except EOFError:
+//!             break
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use nalgebra::DMatrix;
+use num_complex::Complex;
+use serde::Deserialize;
+use serde_pickle::ErrorCode;
+
+use crate::{
+    data::{Exporter, FrequencyResponseData, TransferFunctionData, TransferFunctionDataError},
+    frequency_response::FrequencyResponse,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    #[error("failed to open frequency response stream")]
+    IO(#[from] io::Error),
+    #[error("failed to read or write frequency response stream")]
+    Pickle(#[from] serde_pickle::Error),
+    #[error(r#"frequency response stream must be a pickle file (.pkl), found "{0}""#)]
+    Extension(String),
+    #[error("the checkpoint does not match the frequency sweep")]
+    Checkpoint(#[from] TransferFunctionDataError),
+}
+type Result<T> = std::result::Result<T, StreamError>;
+
+/// Frequency response data type of the streams
+pub type StreamData = FrequencyResponseData<DMatrix<Complex<f64>>>;
+
+/// Frequency response stream writer
+pub struct StreamWriter {
+    buffer: BufWriter<File>,
+    exporter: Exporter,
+}
+impl StreamWriter {
+    /// Creates a new stream starting with the transfer functions metadata
+    pub fn create(path: impl AsRef<Path>, header: &TransferFunctionData) -> Result<Self> {
+        let path = path.as_ref();
+        match path.extension() {
+            Some(ext) if ext == "pkl" => (),
+            ext => {
+                return Err(StreamError::Extension(
+                    ext.map(|ext| ext.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                ));
+            }
+        }
+        Self::new(path, header)
+    }
+    /// Creates a new stream at `path` whatever the file extension
+    fn new(path: &Path, header: &TransferFunctionData) -> Result<Self> {
+        let mut this = Self {
+            buffer: BufWriter::new(File::create(path)?),
+            exporter: Exporter::new(header),
+        };
+        serde_pickle::to_writer(&mut this.buffer, header, Default::default())?;
+        this.buffer.flush()?;
+        Ok(this)
+    }
+    /// Opens an existing stream, with the transfer functions metadata `header`,
+    /// to append frequency responses to it
+    ///
+    /// The phase unwrapping restarts with the first appended frequency response
+    pub fn append(path: impl AsRef<Path>, header: &TransferFunctionData) -> Result<Self> {
+        Ok(Self {
+            buffer: BufWriter::new(OpenOptions::new().append(true).open(path)?),
+            exporter: Exporter::new(header),
+        })
+    }
+    /// Writes a chunk of frequency responses and flushes it to disk
+    pub fn write(&mut self, chunk: &[StreamData]) -> Result<()> {
+        let chunk: Vec<_> = chunk
+            .iter()
+            .cloned()
+            .map(|data| self.exporter.export(data))
+            .collect();
+        serde_pickle::to_writer(&mut self.buffer, &chunk, Default::default())?;
+        self.buffer.flush()?;
+        Ok(())
+    }
+    /// Computes the frequency responses by chunks of `chunk_size` frequencies \[Hz\]
+    /// and writes each chunk to the stream
+    ///
+    /// Only one chunk of frequency responses is held in memory at a time.
+    /// If the phase is unwrapped, the frequencies are sorted and deduplicated first
+    /// as the phase is unwrapped in the order the frequency responses are written
+    pub fn stream<S>(&mut self, system: &S, frequencies: &[f64], chunk_size: usize) -> Result<()>
+    where
+        S: FrequencyResponse<Output = DMatrix<Complex<f64>>> + Sync,
+    {
+        let mut frequencies = frequencies.to_vec();
+        if self.exporter.unwraps_phase() {
+            frequencies.sort_by(f64::total_cmp);
+            frequencies.dedup();
+        }
+        for chunk in frequencies.chunks(chunk_size.max(1)) {
+            let data = system.frequency_responses(chunk.to_vec());
+            self.write(&data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a stream
+///
+/// Returns the transfer functions metadata and the frequency responses with the frequencies in Hz,
+/// the magnitude in linear units and the phase in radians,
+/// an incomplete chunk at the end of the stream (e.g. after a crash) is ignored
+/// but any other error in a chunk is returned
+pub fn read(path: impl AsRef<Path>) -> Result<(TransferFunctionData, Vec<StreamData>)> {
+    let mut de = serde_pickle::Deserializer::new(
+        BufReader::new(File::open(path)?),
+        serde_pickle::DeOptions::new(),
+    );
+    let header = TransferFunctionData::deserialize(&mut de)?.import();
+    let exporter = Exporter::new(&header);
+    let mut data = vec![];
+    loop {
+        de.reset_memo();
+        match Vec::<StreamData>::deserialize(&mut de) {
+            Ok(chunk) => data.extend(chunk.into_iter().map(|data| exporter.import(data))),
+            Err(e) if is_eof(&e) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok((header, data))
+}
+
+/// Checks if a pickle error is the end of the stream, either clean or truncated
+fn is_eof(e: &serde_pickle::Error) -> bool {
+    match e {
+        serde_pickle::Error::Eval(ErrorCode::EOFWhileParsing, _)
+        | serde_pickle::Error::Syntax(ErrorCode::EOFWhileParsing) => true,
+        serde_pickle::Error::Io(e) => e.kind() == io::ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+/// Computes the frequency responses and saves them by chunks of `chunk_size` frequencies \[Hz\]
+/// to the checkpoint stream at `path`
+///
+/// If `resume` is set and the checkpoint exists, the frequencies already in the checkpoint are skipped,
+/// provided that the checkpoint metadata matches the `header`.
+/// The frequency responses are returned in the order of the `frequencies`
+pub fn checkpointed<S>(
+    system: &S,
+    header: &TransferFunctionData,
+    frequencies: &[f64],
+    chunk_size: usize,
+    path: impl AsRef<Path>,
+    resume: bool,
+) -> Result<Vec<StreamData>>
+where
+    S: FrequencyResponse<Output = DMatrix<Complex<f64>>> + Sync,
+{
+    let path = path.as_ref();
+    // the checkpoint frequency responses are kept as computed,
+    // so the frequencies match exactly the frequencies on resume
+    let header = &header.clone().raw();
+    let mut done: HashMap<u64, StreamData> = HashMap::new();
+    if resume && path.exists() {
+        let (checkpoint, data) = read(path)?;
+        header.check_metadata(&checkpoint)?;
+        done.extend(
+            data.into_iter()
+                .filter(|data| frequencies.contains(&data.frequency()))
+                .map(|data| (data.frequency().to_bits(), data)),
+        );
+        println!(
+            "resuming from {:?} with {} frequencies already computed",
+            path,
+            done.len()
+        );
+    }
+    // the checkpoint is rewritten without any incomplete chunk into a temporary file
+    // that replaces the checkpoint only once the frequency responses already computed are saved
+    let tmp = path.with_extension("tmp");
+    let mut writer = StreamWriter::new(&tmp, header)?;
+    if !done.is_empty() {
+        writer.write(&done.values().cloned().collect::<Vec<_>>())?;
+    }
+    drop(writer);
+    fs::rename(&tmp, path)?;
+    let mut writer = StreamWriter::append(path, header)?;
+    let mut todo: Vec<f64> = frequencies
+        .iter()
+        .copied()
+        .filter(|nu| !done.contains_key(&nu.to_bits()))
+        .collect();
+    todo.dedup_by_key(|nu| nu.to_bits());
+    for chunk in todo.chunks(chunk_size.max(1)) {
+        let data = system.frequency_responses(chunk.to_vec());
+        writer.write(&data)?;
+        done.extend(
+            data.into_iter()
+                .map(|data| (data.frequency().to_bits(), data)),
+        );
+    }
+    Ok(frequencies
+        .iter()
+        .filter_map(|nu| done.get(&nu.to_bits()).cloned())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{frequency_response::FrequencyUnit, test_utils::TempFile};
+
+    use super::*;
+
+    #[test]
+    fn resume() {
+        struct Gain;
+        impl FrequencyResponse for Gain {
+            type Output = DMatrix<Complex<f64>>;
+            fn j_omega(&self, jw: Complex<f64>) -> Self::Output {
+                DMatrix::from_element(1, 1, jw)
+            }
+        }
+        let path = TempFile::new("checkpoint.pkl");
+        let header = TransferFunctionData::default();
+        let frequencies = vec![1., 2., 3., 4.];
+        let partial = checkpointed(&Gain, &header, &frequencies[..2], 1, &path, false).unwrap();
+        assert_eq!(partial.len(), 2);
+        let data = checkpointed(&Gain, &header, &frequencies, 1, &path, true).unwrap();
+        let nu: Vec<_> = data.iter().map(|data| data.frequency()).collect();
+        assert_eq!(nu, frequencies);
+        assert!(!path.as_ref().with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn unwrapped_phase() {
+        struct Delay;
+        impl FrequencyResponse for Delay {
+            type Output = DMatrix<Complex<f64>>;
+            fn j_omega(&self, jw: Complex<f64>) -> Self::Output {
+                DMatrix::from_element(1, 1, (-jw * 1e-2).exp())
+            }
+        }
+        let path = TempFile::new("unwrapped.pkl");
+        let header = TransferFunctionData::default().unwrapped_phase(true);
+        StreamWriter::create(&path, &header)
+            .unwrap()
+            .stream(&Delay, &[30., 10., 20., 10.], 1)
+            .unwrap();
+        let (_, data) = read(&path).unwrap();
+        let nu: Vec<_> = data.iter().map(|data| data.frequency()).collect();
+        assert_eq!(nu, vec![10., 20., 30.]);
+    }
+
+    #[test]
+    fn write_read() {
+        let path = TempFile::new("stream.pkl");
+        let data = |nu: f64| StreamData::new(nu, DMatrix::from_element(2, 3, Complex::new(nu, 1.)));
+        let header = TransferFunctionData::default().frequency_unit(FrequencyUnit::RadPerSecond);
+        let mut stream = StreamWriter::create(&path, &header).unwrap();
+        stream.write(&[data(1.), data(2.)]).unwrap();
+        stream.write(&[data(3.)]).unwrap();
+        drop(stream);
+        // incomplete chunk
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[0x80, 0x03, 0x5d])
+            .unwrap();
+        let (header, data) = read(&path).unwrap();
+        assert_eq!(header.frequency_unit, FrequencyUnit::RadPerSecond);
+        let nu: Vec<_> = data.iter().map(|data| data.frequency()).collect();
+        assert_eq!(nu.len(), 3);
+        assert!(
+            nu.iter()
+                .zip([1., 2., 3.])
+                .all(|(nu, expected)| (nu - expected).abs() < 1e-12)
+        );
+    }
+
+    #[test]
+    fn corrupted() {
+        let path = TempFile::new("corrupted.pkl");
+        let header = TransferFunctionData::default();
+        let mut stream = StreamWriter::create(&path, &header).unwrap();
+        stream
+            .write(&[StreamData::new(
+                1.,
+                DMatrix::from_element(1, 1, Complex::new(1., 1.)),
+            )])
+            .unwrap();
+        drop(stream);
+        // unsupported opcode
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[0x80, 0x03, 0xff])
+            .unwrap();
+        let result = read(&path);
+        assert!(matches!(result, Err(StreamError::Pickle(_))));
+    }
+}
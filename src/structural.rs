@@ -1,40 +1,82 @@
 //! FEM structural dynamic model
 
-use std::{f64::consts, fmt::Display};
+use std::{
+    collections::BTreeMap,
+    env,
+    f64::consts,
+    fmt::Display,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use gmt_dos_clients_fem::{Model, Switch};
 use gmt_fem::FEM;
+use indicatif::ParallelProgressIterator;
 use nalgebra::{DMatrix, DMatrixView};
 use num_complex::Complex;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::frequency_response::{FrequencyResponse, if64};
+use crate::{
+    damping::{DampingError, DampingModel, DampingSchedule},
+    data::FrequencyResponseData,
+    discovery::{self, FemIo, FemIoNames, Suggestions},
+    frequency_response::{FrequencyResponse, if64, progress_style},
+    modal_model::{ModalModel, ModalModelError},
+    selection::Channels,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum StructuralError {
     #[error(transparent)]
     FEM(#[from] gmt_fem::FemError),
-    // #[error(transparent)]
-    // Decode(#[from] bincode::error::DecodeError),
-    // #[error(transparent)]
-    // Encode(#[from] bincode::error::EncodeError),
+    #[error(transparent)]
+    Encode(#[from] bincode::error::EncodeError),
     #[error(transparent)]
     IO(#[from] std::io::Error),
-    #[error("inputs and outputs do not match model in {0}")]
-    IOMismatch(String),
+    #[error(
+        "failed to get the FEM name from the FEM_REPO environment variable, set it to the FEM directory or use --fem"
+    )]
+    FemRepo(#[from] env::VarError),
+    #[error("failed to set the modal damping coefficients")]
+    Damping(#[from] DampingError),
+    #[error("{0} requires the FEM static gain")]
+    MissingStaticGain(&'static str),
+    #[error("failed to load the modal model")]
+    ModalModel(#[from] ModalModelError),
+    #[error(
+        "the minimum eigen frequency ({min}Hz) must be less than or equal to the maximum eigen frequency ({max}Hz)"
+    )]
+    EigenWindow { min: f64, max: f64 },
+    #[error("there is no FEM eigen frequencies within [{min:?},{max:?}]Hz")]
+    EmptyEigenWindow { min: Option<f64>, max: Option<f64> },
+    #[error(
+        "the residual static correction and the static gain mismatch compensation cannot be used together"
+    )]
+    StaticGainCorrections,
+    #[error(r#"unknown FEM input: "{0}"{1}"#)]
+    UnknownInput(String, Suggestions),
+    #[error(r#"unknown FEM output: "{0}"{1}"#)]
+    UnknownOutput(String, Suggestions),
 }
 type Result<T> = std::result::Result<T, StructuralError>;
 
+/// Static gain mismatch compensation
+///
+/// Difference between the FEM static solution gain and the FEM dynamic solution static gain
+/// with an optional delay
 #[derive(Debug, Deserialize, Serialize)]
 pub struct StaticGainCompensation {
     pub(crate) delay: Option<f64>,
-    pub(crate) delta_gain: DMatrix<if64>,
+    pub(crate) delta_gain: DMatrix<f64>,
 }
 impl Default for StaticGainCompensation {
     fn default() -> Self {
         Self {
             delay: Default::default(),
-            delta_gain: DMatrix::<if64>::zeros(1, 1),
+            delta_gain: DMatrix::<f64>::zeros(1, 1),
         }
     }
 }
@@ -42,42 +84,151 @@ impl Default for StaticGainCompensation {
 /// FEM structural dynamic model
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Structural {
+    // model name
+    pub(crate) model: String,
     // inputs labels
     pub(crate) inputs: Vec<String>,
+    // inputs number of channels
+    pub(crate) inputs_size: Vec<usize>,
     // outputs labels
     pub(crate) outputs: Vec<String>,
+    // outputs number of channels
+    pub(crate) outputs_size: Vec<usize>,
+    // FEM channels indices of the inputs channels selections, empty if all the channels are selected
+    pub(crate) inputs_channels: Vec<Vec<usize>>,
+    // FEM channels indices of the outputs channels selections, empty if all the channels are selected
+    pub(crate) outputs_channels: Vec<Vec<usize>>,
     // modal forces matrix
-    pub(crate) b: DMatrix<if64>,
+    pub(crate) b: DMatrix<f64>,
     // modal displacements matrix
-    pub(crate) c: DMatrix<if64>,
+    pub(crate) c: DMatrix<f64>,
     // static solution gain matrix
     pub(crate) g_ssol: Option<DMatrix<f64>>,
     // static gain mismatch compensation scheme
     pub(crate) static_gain_mismatch: Option<StaticGainCompensation>,
+    // residual static gain of the truncated modes (mode acceleration)
+    pub(crate) residual: Option<DMatrix<f64>>,
     // eigen frequencies
     pub(crate) w: Vec<f64>,
-    // damping coefficient
-    pub(crate) z: f64,
+    // damping model
+    pub(crate) damping_model: DampingModel,
+    // damping schedule
+    pub(crate) damping: DampingSchedule,
+    // modal damping coefficients
+    pub(crate) z: Vec<f64>,
     // optical sensitivity matrix
     pub(crate) optical_senses: Option<DMatrix<f64>>,
+    // model fingerprint
+    pub(crate) fingerprint: StructuralCacheKey,
+}
+
+/// [Structural] on-disk cache policy
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// loads [Structural] from the cache if the key matches, otherwise builds and saves it
+    #[default]
+    Enabled,
+    /// always builds [Structural] from the FEM and leaves the cache untouched
+    Bypass,
+    /// deletes the cache, then builds and saves [Structural]
+    Clear,
+}
+
+/// [Structural] cache key
+///
+/// A cached model is reused only if all the fields match
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StructuralCacheKey {
+    fem: String,
+    fem_size: u64,
+    fem_modified: Option<SystemTime>,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    min_eigen_frequency: Option<f64>,
+    max_eigen_frequency: Option<f64>,
+    damping_model: DampingModel,
+    damping: DampingSchedule,
+    static_gain_mismatch: Option<Option<f64>>,
+    residual_correction: bool,
+    inputs_channels: BTreeMap<String, Channels>,
+    outputs_channels: BTreeMap<String, Channels>,
+    modal_model: Option<PathBuf>,
+}
+
+/// Number of rigid body modes of the FEM
+///
+/// The first modes of the FEM are the rigid body modes, they are excluded from
+/// the FEM dynamic solution static gain as in [FEM::static_gain]
+const FEM_RIGID_BODY_MODES: usize = 3;
+
+/// FEM model file in the `FEM_REPO` directory
+const FEM_MODEL_FILE: &str = "modal_state_space_model_2ndOrder.zip";
+
+/// Returns the name of the FEM given by the `FEM_REPO` environment variable
+pub(crate) fn fem_name() -> Result<String> {
+    let fem_repo = env::var("FEM_REPO")?;
+    Ok(Path::new(&fem_repo)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(fem_repo))
+}
+
+/// Returns the size and the last modification time of the FEM model file
+/// in the directory given by the `FEM_REPO` environment variable
+fn fem_model_file_stamp() -> Result<(u64, Option<SystemTime>)> {
+    let metadata = fs::metadata(Path::new(&env::var("FEM_REPO")?).join(FEM_MODEL_FILE))?;
+    Ok((metadata.len(), metadata.modified().ok()))
+}
+
+/// Replaces the `names`, and the keys of their `channels` selections,
+/// with the names of the matching FEM inputs or outputs
+///
+/// Returns the first unknown name and the closest FEM names
+fn resolve_fem_names(
+    names: &mut [String],
+    channels: &mut BTreeMap<String, Channels>,
+    ios: &[FemIo],
+) -> std::result::Result<(), (String, Suggestions)> {
+    let candidates = discovery::candidates(ios);
+    for name in names.iter_mut() {
+        let fem_name = discovery::lookup(name, &candidates)
+            .map_err(|suggestions| (name.clone(), suggestions))?;
+        if let Some(selection) = channels.remove(name.as_str()) {
+            channels.insert(fem_name.clone(), selection);
+        }
+        *name = fem_name;
+    }
+    Ok(())
 }
 
 /// FEM structural dynamic model builder
 #[derive(Debug, Default)]
 pub struct StructuralBuilder {
-    // inputs: Vec<String>,
-    // outputs: Vec<String>,
-    // z: f64,
     pub(crate) built: Structural,
     pub(crate) min_eigen_frequency: Option<f64>,
     pub(crate) max_eigen_frequency: Option<f64>,
     pub(crate) file_name: String,
-    // static_gain_mismatch: Option<StaticGainCompensation>,
+    pub(crate) cache: CachePolicy,
+    pub(crate) static_gain_mismatch: Option<StaticGainCompensation>,
+    pub(crate) residual_correction: bool,
+    pub(crate) modal_model: Option<PathBuf>,
+    pub(crate) inputs_channels: BTreeMap<String, Channels>,
+    pub(crate) outputs_channels: BTreeMap<String, Channels>,
 }
 impl StructuralBuilder {
-    /// Sets the FEM modal damping coefficient
-    pub fn damping(mut self, z: f64) -> Self {
-        self.built.z = z;
+    /// Sets the FEM modal damping schedule
+    ///
+    /// A single damping coefficient applies to all the modes
+    pub fn damping(mut self, damping: impl Into<DampingSchedule>) -> Self {
+        self.built.damping = damping.into();
+        self
+    }
+    /// Sets the FEM modal damping model
+    ///
+    /// The damping schedule coefficients are the viscous damping ratios
+    /// or the hysteretic loss factors according to the model
+    pub fn damping_model(mut self, damping_model: DampingModel) -> Self {
+        self.built.damping_model = damping_model;
         self
     }
     /// Truncates the eigen frequencies to and including `max_eigen_frequency`
@@ -95,31 +246,81 @@ impl StructuralBuilder {
         self
     }
     /// Sets the filename where [Structural] is seralize to
+    ///
+    /// The file is saved in the directory given by the `DATA_REPO` environment variable
+    /// or in the current directory if `DATA_REPO` is not set
     pub fn filename<S: Into<String>>(mut self, file_name: S) -> Self {
         self.file_name = file_name.into();
         self
     }
+    /// Sets the [Structural] cache policy
+    pub fn cache(mut self, cache: CachePolicy) -> Self {
+        self.cache = cache;
+        self
+    }
     pub fn optical_sensitivities(mut self, mat: Option<DMatrix<f64>>) -> Self {
         self.built.optical_senses = mat;
         self
     }
-    /* /// Enables the compensation of the static gain mismatch
+    /// Enables the compensation of the static gain mismatch
     ///
-    /// An optional delay `s``:w` may be added
-    fn enable_static_gain_mismatch_compensation(mut self, maybe_delay: Option<f64>) -> Self {
-        self.static_gain_mismatch = Some(Default::default());
-        if let Some(value) = maybe_delay {
-            self.static_gain_mismatch
-                .as_mut()
-                .and_then(|sgm| sgm.delay.replace(value));
-        }
+    /// The difference between the FEM static solution gain and the FEM dynamic
+    /// solution static gain is added to the frequency response,
+    /// an optional delay `t_s` \[s\] may be applied to the difference
+    pub fn enable_static_gain_mismatch_compensation(mut self, maybe_delay: Option<f64>) -> Self {
+        self.static_gain_mismatch = Some(StaticGainCompensation {
+            delay: maybe_delay,
+            ..Default::default()
+        });
+        self
+    }
+    /// Enables the residual static correction (mode acceleration)
+    ///
+    /// The difference between the FEM static solution gain and the static gain of the
+    /// retained modes and of the modes below the eigen frequency window,
+    /// i.e. the quasi-static contribution of the modes above the window,
+    /// is added to the frequency response.
+    /// It cannot be used together with the static gain mismatch compensation
+    /// as both add the static gain of the truncated modes
+    pub fn enable_residual_correction(mut self) -> Self {
+        self.residual_correction = true;
+        self
+    }
+    /// Builds [Structural] from a [modal model](crate::modal_model) file instead of the FEM
+    ///
+    /// The builder inputs and outputs select the inputs and outputs of the modal model,
+    /// all of them are used if none are given
+    pub fn modal_model(mut self, path: impl AsRef<Path>) -> Self {
+        self.modal_model = Some(path.as_ref().to_path_buf());
+        self
+    }
+    /// Selects the channels of an input
+    ///
+    /// The columns of the modal forces matrix of the other channels are dropped
+    pub fn input_channels(
+        mut self,
+        name: impl Into<String>,
+        channels: impl Into<Channels>,
+    ) -> Self {
+        self.inputs_channels.insert(name.into(), channels.into());
         self
-    } */
+    }
+    /// Selects the channels of an output
+    ///
+    /// The rows of the modal displacements matrix of the other channels are dropped
+    pub fn output_channels(
+        mut self,
+        name: impl Into<String>,
+        channels: impl Into<Channels>,
+    ) -> Self {
+        self.outputs_channels.insert(name.into(), channels.into());
+        self
+    }
     fn new(inputs: Vec<String>, outputs: Vec<String>) -> Self {
         let built = Structural {
             inputs,
             outputs,
-            z: 2. / 100.,
+            damping: DampingSchedule::Constant(2. / 100.),
             ..Default::default()
         };
         Self {
@@ -128,112 +329,311 @@ impl StructuralBuilder {
             ..Default::default()
         }
     }
+    /// Returns the path to the [Structural] cache file
+    pub fn cache_path(&self) -> PathBuf {
+        let repo = env::var("DATA_REPO").unwrap_or_else(|_| ".".to_string());
+        let file_name = if self.file_name.is_empty() {
+            "structural"
+        } else {
+            self.file_name.as_str()
+        };
+        Path::new(&repo).join(file_name).with_extension("bin")
+    }
+    /// Returns the [Structural] cache key
+    ///
+    /// The FEM is identified by its name and by the size and the last modification time
+    /// of its model file, so a FEM re-exported at the same location invalidates the cache
+    pub fn cache_key(&self) -> Result<StructuralCacheKey> {
+        let (fem_size, fem_modified) = fem_model_file_stamp()?;
+        Ok(StructuralCacheKey {
+            fem: fem_name()?,
+            fem_size,
+            fem_modified,
+            ..self.fingerprint()
+        })
+    }
+    /// Returns the [Structural] model fingerprint
+    ///
+    /// It is the cache key, without the FEM model file stamp if the file cannot be stamped
+    pub fn fingerprint(&self) -> StructuralCacheKey {
+        let (fem_size, fem_modified) = fem_model_file_stamp().unwrap_or_default();
+        StructuralCacheKey {
+            fem: fem_name().unwrap_or_default(),
+            fem_size,
+            fem_modified,
+            inputs: self.built.inputs.clone(),
+            outputs: self.built.outputs.clone(),
+            min_eigen_frequency: self.min_eigen_frequency,
+            max_eigen_frequency: self.max_eigen_frequency,
+            damping_model: self.built.damping_model,
+            damping: self.built.damping.clone(),
+            static_gain_mismatch: self.static_gain_mismatch.as_ref().map(|sgm| sgm.delay),
+            residual_correction: self.residual_correction,
+            inputs_channels: self.inputs_channels.clone(),
+            outputs_channels: self.outputs_channels.clone(),
+            modal_model: self.modal_model.clone(),
+        }
+    }
+    /// Loads [Structural] from the cache if the cache key matches
+    fn load_cache(&self, path: &Path, key: &StructuralCacheKey) -> Option<Structural> {
+        let file = File::open(path).ok()?;
+        let mut buffer = BufReader::new(file);
+        match bincode::serde::decode_from_std_read::<(StructuralCacheKey, Structural), _, _>(
+            &mut buffer,
+            bincode::config::standard(),
+        ) {
+            Ok((cached_key, this)) if cached_key == *key => {
+                println!("loading structural from {:?}", path);
+                Some(this)
+            }
+            Ok(_) => {
+                println!("structural cache {:?} is stale, rebuilding", path);
+                None
+            }
+            Err(e) => {
+                println!(
+                    "failed to decode structural cache {:?} ({e}), rebuilding",
+                    path
+                );
+                None
+            }
+        }
+    }
+    /// Saves [Structural] and its key to the cache
+    fn save_cache(path: &Path, key: &StructuralCacheKey, this: &Structural) -> Result<()> {
+        let file = File::create(path)?;
+        let mut buffer = BufWriter::new(file);
+        bincode::serde::encode_into_std_write(
+            (key, this),
+            &mut buffer,
+            bincode::config::standard(),
+        )?;
+        println!("structural saved to {:?}", path);
+        Ok(())
+    }
     /// Builds the [Structural] model
+    ///
+    /// Depending on the [CachePolicy], the model derived from the FEM is loaded from or saved to
+    /// the file given by [cache_path](StructuralBuilder::cache_path),
+    /// a model built from a [modal model](StructuralBuilder::modal_model) file is never cached.
+    /// If the FEM model file cannot be identified, e.g. it is missing, the cache is bypassed.
+    /// A failure to save the cache is reported but does not fail the build
     pub fn build(self) -> Result<Structural> {
-        // let repo = env::var("DATA_REPO").unwrap_or_else(|_| ".".to_string());
-        // let path = Path::new(&repo).join(self.file_name).with_extension("bin");
-        // if let Ok(file) = File::open(&path) {
-        //     println!("loading structural from {:?}", path);
-        //     let buffer = BufReader::new(file);
-        //     let this: Structural =
-        //         bincode::serde::decode_from_reader(buffer, bincode::config::standard())?;
-        //     if !(this.inputs == self.inputs && this.outputs == self.outputs) {
-        //         return Err(StructuralError::IOMismatch(
-        //             path.to_str().unwrap().to_string(),
-        //         ));
-        //     }
-        //     Ok(this)
-        // } else {
+        if let (Some(min), Some(max)) = (self.min_eigen_frequency, self.max_eigen_frequency)
+            && min > max
+        {
+            return Err(StructuralError::EigenWindow { min, max });
+        }
+        if self.residual_correction && self.static_gain_mismatch.is_some() {
+            return Err(StructuralError::StaticGainCorrections);
+        }
+        if self.cache == CachePolicy::Bypass || self.modal_model.is_some() {
+            return self.build_from_modal_model();
+        }
+        let path = self.cache_path();
+        if self.cache == CachePolicy::Clear && path.exists() {
+            fs::remove_file(&path)?;
+            println!("structural cache {:?} cleared", path);
+        }
+        let key = match self.cache_key() {
+            Ok(key) => key,
+            Err(e) => {
+                println!(
+                    "failed to identify the FEM for the structural cache ({e}), bypassing the cache"
+                );
+                return self.build_from_modal_model();
+            }
+        };
+        if let Some(this) = self.load_cache(&path, &key) {
+            return Ok(Structural {
+                optical_senses: self.built.optical_senses,
+                ..this
+            });
+        }
+        let this = self.build_from_modal_model()?;
+        if let Err(e) = Self::save_cache(&path, &key, &this) {
+            println!("failed to save structural to {:?} ({e})", path);
+        }
+        Ok(this)
+    }
+    /// Derives the modal model from the FEM
+    ///
+    /// The inputs and outputs, and the keys of their channels selections,
+    /// are replaced with the names of the matching FEM inputs and outputs
+    fn modal_model_from_fem(&mut self) -> Result<ModalModel> {
         println!("building structural from FEM");
         let mut fem = FEM::from_env()?;
         println!("{fem}");
 
+        let io = FemIoNames::from(&fem);
+        resolve_fem_names(
+            &mut self.built.inputs,
+            &mut self.inputs_channels,
+            &io.inputs,
+        )
+        .map_err(|(name, suggestions)| StructuralError::UnknownInput(name, suggestions))?;
+        resolve_fem_names(
+            &mut self.built.outputs,
+            &mut self.outputs_channels,
+            &io.outputs,
+        )
+        .map_err(|(name, suggestions)| StructuralError::UnknownOutput(name, suggestions))?;
+
         fem.switch_inputs(Switch::Off, None)
             .switch_inputs_by_name(self.built.inputs.clone(), Switch::On)?
             .switch_outputs(Switch::Off, None)
             .switch_outputs_by_name(self.built.outputs.clone(), Switch::On)?;
-        let b = DMatrix::<f64>::from_row_slice(fem.n_modes(), fem.n_inputs(), &fem.inputs2modes())
-            .map(|x| Complex::new(x, 0f64));
+        let (inputs, inputs_size) = fem
+            .inputs
+            .iter()
+            .filter_map(|x| x.as_ref())
+            .filter(|x| x.len() > 0)
+            .map(|x| (x.name().to_string(), x.len()))
+            .unzip();
+        let (outputs, outputs_size) = fem
+            .outputs
+            .iter()
+            .filter_map(|x| x.as_ref())
+            .filter(|x| x.len() > 0)
+            .map(|x| (x.name().to_string(), x.len()))
+            .unzip();
+        let b = DMatrix::<f64>::from_row_slice(fem.n_modes(), fem.n_inputs(), &fem.inputs2modes());
         let c =
-            DMatrix::<f64>::from_row_slice(fem.n_outputs(), fem.n_modes(), &fem.modes2outputs())
-                .map(|x| Complex::new(x, 0f64));
-        let g_ssol = fem.reduced_static_gain();
-        let w = fem.eigen_frequencies_to_radians();
-
-        // self.static_gain_mismatch.as_mut().map(|sgm| {
-        //     let g_dsol = fem.static_gain();
-        //     let delta_g = g_ssol.as_ref().expect("failed to get FEM static gain") - g_dsol;
-        //     sgm.delta_gain = delta_g.map(|x| Complex::new(x, 0f64));
-        // });
-
-        let q = match (self.min_eigen_frequency, self.max_eigen_frequency) {
-            (Some(min), Some(max)) => Some((
-                fem.eigen_frequencies
-                    .iter()
-                    .copied()
-                    .enumerate()
-                    .find(|(_, f)| *f >= min)
-                    .unwrap_or_default()
-                    .0,
-                fem.eigen_frequencies
-                    .iter()
-                    .copied()
-                    .filter_map(|f| (f >= min && f <= max).then(|| f))
-                    .enumerate()
-                    .last()
-                    .unwrap_or_default()
-                    .0
-                    + 1,
-            )),
-            (None, Some(max)) => Some((
-                0,
-                fem.eigen_frequencies
-                    .iter()
-                    .copied()
-                    .filter_map(|f| (f <= max).then(|| f))
-                    .enumerate()
-                    .last()
-                    .unwrap_or_default()
-                    .0
-                    + 1,
-            )),
-            (Some(min), None) => {
-                let s = fem
-                    .eigen_frequencies
-                    .iter()
-                    .copied()
-                    .enumerate()
-                    .find(|(_, f)| *f >= min)
-                    .unwrap_or_default()
-                    .0;
-                Some((s, fem.eigen_frequencies.len() - s))
+            DMatrix::<f64>::from_row_slice(fem.n_outputs(), fem.n_modes(), &fem.modes2outputs());
+        Ok(ModalModel {
+            name: fem_name()?,
+            inputs,
+            inputs_size,
+            outputs,
+            outputs_size,
+            static_gain: fem.reduced_static_gain(),
+            eigen_frequencies: fem.eigen_frequencies,
+            b,
+            c,
+            rigid_body_modes: Some(FEM_RIGID_BODY_MODES),
+        })
+    }
+    /// Builds the [Structural] model from either the FEM or a modal model file
+    fn build_from_modal_model(mut self) -> Result<Structural> {
+        let fingerprint = self.fingerprint();
+        let model = match self.modal_model.clone() {
+            Some(path) => {
+                println!("building structural from {:?}", path);
+                ModalModel::load(path)?.select(&self.built.inputs, &self.built.outputs)?
             }
-            (None, None) => None,
+            None => self.modal_model_from_fem()?,
         };
+        Ok(Structural {
+            fingerprint,
+            ..self.build_from(model)?
+        })
+    }
+    /// Builds the [Structural] model from a modal model
+    fn build_from(self, model: ModalModel) -> Result<Structural> {
+        let selected =
+            |labels: &[String], selection: &BTreeMap<String, Channels>| -> Vec<Vec<usize>> {
+                labels
+                    .iter()
+                    .map(|label| {
+                        selection
+                            .get(label)
+                            .map(|channels| channels.to_vec())
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            };
+        let inputs_channels = selected(&model.inputs, &self.inputs_channels);
+        let outputs_channels = selected(&model.outputs, &self.outputs_channels);
+        let model = model.select_channels(&self.inputs_channels, &self.outputs_channels)?;
+        let w: Vec<_> = model
+            .eigen_frequencies
+            .iter()
+            .map(|x| 2.0 * consts::PI * x)
+            .collect();
+        let z = self.built.damping.modal_coefficients(&w)?;
 
-        Ok(if let Some((s, n)) = q {
-            Structural {
-                b: b.rows(s, n).into_owned(),
-                c: c.columns(s, n).into_owned(),
-                g_ssol,
-                w: w[s..s + n].to_vec(),
-                ..self.built
-            }
+        let static_gain_mismatch = self
+            .static_gain_mismatch
+            .map(|sgm| -> Result<StaticGainCompensation> {
+                let g_dsol = model.modal_static_gain();
+                let delta_gain =
+                    model
+                        .static_gain
+                        .as_ref()
+                        .ok_or(StructuralError::MissingStaticGain(
+                            "static gain mismatch compensation",
+                        ))?
+                        - g_dsol;
+                Ok(StaticGainCompensation { delta_gain, ..sgm })
+            })
+            .transpose()?;
+
+        // modes within the eigen frequency window
+        let (min, max) = (self.min_eigen_frequency, self.max_eigen_frequency);
+        let in_window =
+            |f: &f64| min.is_none_or(|min| *f >= min) && max.is_none_or(|max| *f <= max);
+        let q = if min.is_some() || max.is_some() {
+            let s = model
+                .eigen_frequencies
+                .iter()
+                .position(in_window)
+                .unwrap_or_default();
+            let n = model
+                .eigen_frequencies
+                .iter()
+                .filter(|f| in_window(*f))
+                .count();
+            Some((s, n))
         } else {
-            Structural {
-                b,
-                c,
-                g_ssol,
-                w,
-                ..self.built
-            }
+            None
+        };
+        if q.is_some_and(|(_, n)| n == 0) || w.is_empty() {
+            return Err(StructuralError::EmptyEigenWindow { min, max });
+        }
+
+        // quasi-static contribution of the modes above the eigen frequency window,
+        // the modes below the window are dropped without correction
+        let residual = self
+            .residual_correction
+            .then(|| -> Result<DMatrix<f64>> {
+                let g = model
+                    .static_gain
+                    .as_ref()
+                    .ok_or(StructuralError::MissingStaticGain(
+                        "residual static correction",
+                    ))?;
+                let end = q.map_or(model.eigen_frequencies.len(), |(s, n)| s + n);
+                Ok(g - model.modes_static_gain(0..end))
+            })
+            .transpose()?;
+
+        let (model, w, z) = if let Some((s, n)) = q {
+            (
+                model.truncate(s, n),
+                w[s..s + n].to_vec(),
+                z[s..s + n].to_vec(),
+            )
+        } else {
+            (model, w, z)
+        };
+
+        Ok(Structural {
+            model: model.name,
+            inputs: model.inputs,
+            inputs_size: model.inputs_size,
+            outputs: model.outputs,
+            outputs_size: model.outputs_size,
+            inputs_channels,
+            outputs_channels,
+            b: model.b,
+            c: model.c,
+            g_ssol: model.static_gain,
+            static_gain_mismatch,
+            residual,
+            w,
+            z,
+            ..self.built
         })
-        // let file = File::create(&path)?;
-        // let mut buffer = BufWriter::new(file);
-        // bincode::serde::encode_into_std_write(&this, &mut buffer, bincode::config::standard())?;
-        // println!("structural save to {:?}", path);
-        // Ok(this)
-        // }
     }
 }
 impl Structural {
@@ -260,67 +660,307 @@ impl Structural {
 
 impl Display for Structural {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "GMT structural dynamic model:")?;
+        writeln!(f, "GMT structural dynamic model ({}):", self.model)?;
         writeln!(f, " + inputs: {:?}", self.inputs)?;
         writeln!(f, " + outputs: {:?}", self.outputs)?;
-        writeln!(
-            f,
-            " + eigen frequencies: ({:.3},{:.3})Hz",
-            0.5 * self.w[0] * consts::FRAC_1_PI,
-            0.5 * self.w.last().unwrap() * consts::FRAC_1_PI
-        )?;
-        writeln!(f, " + damping: {:}%", self.z * 1e2)?;
+        match (self.w.first(), self.w.last()) {
+            (Some(w_min), Some(w_max)) => writeln!(
+                f,
+                " + eigen frequencies: ({:.3},{:.3})Hz",
+                0.5 * w_min * consts::FRAC_1_PI,
+                0.5 * w_max * consts::FRAC_1_PI
+            )?,
+            _ => writeln!(f, " + eigen frequencies: none")?,
+        }
+        writeln!(f, " + damping: {} ({})", self.damping, self.damping_model)?;
         writeln!(f, " + B matrix {:?}", self.b.shape())?;
         writeln!(f, " + C matrix {:?}", self.c.shape())?;
         if let Some(g) = self.g_ssol.as_ref() {
             writeln!(f, " + static gain matrix {:?}", g.shape())?;
         }
+        if self.residual.is_some() {
+            writeln!(f, " + residual static correction")?;
+        }
+        match &self.static_gain_mismatch {
+            Some(StaticGainCompensation { delay: None, .. }) => {
+                writeln!(f, " + static gain mismatch compensation")?
+            }
+            Some(StaticGainCompensation {
+                delay: Some(t_s), ..
+            }) => writeln!(
+                f,
+                " + static gain mismatch compensation with {:.3}ms delay",
+                t_s * 1e3
+            )?,
+            None => (),
+        }
         Ok(())
     }
 }
 
-impl FrequencyResponse for Structural {
-    type Output = DMatrix<Complex<f64>>;
+/// Per-thread workspace of the [Structural] frequency response
+///
+/// The buffers are allocated once per thread and reused for every frequency
+#[derive(Debug, Clone)]
+pub struct StructuralBuffer {
+    // C·diag(1/ode) real and imaginary parts
+    cd_re: DMatrix<f64>,
+    cd_im: DMatrix<f64>,
+    // frequency response real and imaginary parts
+    re: DMatrix<f64>,
+    im: DMatrix<f64>,
+}
+impl StructuralBuffer {
+    /// Allocates the buffers for the given [Structural] model
+    pub fn new(structural: &Structural) -> Self {
+        let (ny, n) = structural.c.shape();
+        let nu = structural.b.ncols();
+        Self {
+            cd_re: DMatrix::zeros(ny, n),
+            cd_im: DMatrix::zeros(ny, n),
+            re: DMatrix::zeros(ny, nu),
+            im: DMatrix::zeros(ny, nu),
+        }
+    }
+}
 
-    /// *Dynamics and Control of Structures, W.K. Gawronsky*, p.17-18, Eqs.(2.21)-(2.22)
-    fn j_omega(&self, jw: if64) -> Self::Output {
-        let zeros = DMatrix::<Complex<f64>>::zeros(self.c.nrows(), self.b.ncols());
-        let fr = self
-            .c
-            .column_iter()
-            .zip(self.b.row_iter())
-            .zip(&self.w)
-            .fold(zeros, |a, ((c, b), wi)| {
-                let mut cb = c * b;
-                let ode = wi * wi + jw * jw + 2f64 * self.z * wi * jw;
-                cb /= ode;
-                a + cb
-            });
-        let fr = match &self.static_gain_mismatch {
+impl Structural {
+    /// Returns the frequency response using the preallocated buffers
+    ///
+    /// The frequency response is computed as `C·diag(1/ode)·B` with 2 real matrix products,
+    /// one for the real part and one for the imaginary part
+    pub fn j_omega_with(&self, jw: if64, buffer: &mut StructuralBuffer) -> DMatrix<if64> {
+        let StructuralBuffer {
+            cd_re,
+            cd_im,
+            re,
+            im,
+        } = buffer;
+        for (k, (wi, zi)) in self.w.iter().zip(&self.z).enumerate() {
+            let ode = match self.damping_model {
+                DampingModel::Viscous => wi * wi + jw * jw + 2f64 * zi * wi * jw,
+                DampingModel::Hysteretic => wi * wi * Complex::new(1f64, *zi) + jw * jw,
+            };
+            let d = ode.inv();
+            let c = self.c.column(k);
+            cd_re
+                .column_mut(k)
+                .iter_mut()
+                .zip(c.iter())
+                .for_each(|(x, c)| *x = c * d.re);
+            cd_im
+                .column_mut(k)
+                .iter_mut()
+                .zip(c.iter())
+                .for_each(|(x, c)| *x = c * d.im);
+        }
+        re.gemm(1f64, cd_re, &self.b, 0f64);
+        im.gemm(1f64, cd_im, &self.b, 0f64);
+        if let Some(residual) = &self.residual {
+            *re += residual;
+        }
+        match &self.static_gain_mismatch {
             Some(StaticGainCompensation {
                 delay: None,
                 delta_gain,
-            }) => fr + delta_gain,
+            }) => *re += delta_gain,
             Some(StaticGainCompensation {
                 delay: Some(t_s),
                 delta_gain,
-            }) => fr + (delta_gain * (-jw * t_s).exp()),
-            None => fr,
+            }) => {
+                let e = (-jw * t_s).exp();
+                re.zip_apply(delta_gain, |x, g| *x += g * e.re);
+                im.zip_apply(delta_gain, |x, g| *x += g * e.im);
+            }
+            None => (),
         };
-        if let Some(mat) = self.optical_senses.as_ref() {
-            mat.map(|x| Complex::from(x)) * fr
-        } else {
-            fr
+        match self.optical_senses.as_ref() {
+            Some(mat) => (mat * &*re).zip_map(&(mat * &*im), Complex::new),
+            None => re.zip_map(im, Complex::new),
         }
     }
 }
 
+impl FrequencyResponse for Structural {
+    type Output = DMatrix<Complex<f64>>;
+
+    /// *Dynamics and Control of Structures, W.K. Gawronsky*, p.17-18, Eqs.(2.21)-(2.22)
+    fn j_omega(&self, jw: if64) -> Self::Output {
+        self.j_omega_with(jw, &mut StructuralBuffer::new(self))
+    }
+    /// Returns the eigen frequencies \[Hz\]
+    fn resonance_frequencies(&self) -> Vec<f64> {
+        self.eigen_frequencies_hz()
+    }
+    /// Computes the frequency responses in parallel with one [StructuralBuffer] per thread
+    fn frequency_responses(&self, nu: Vec<f64>) -> Vec<FrequencyResponseData<Self::Output>> {
+        nu.into_par_iter()
+            .progress_with_style(progress_style())
+            .map_init(
+                || StructuralBuffer::new(self),
+                |buffer, nu| {
+                    let jw = Complex::new(0f64, 2f64 * consts::PI * nu);
+                    FrequencyResponseData::new(nu, self.j_omega_with(jw, buffer))
+                },
+            )
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::frequency_response::Frequencies;
+    use crate::{data::TransferFunctionData, frequency_response::Frequencies};
 
     use super::*;
 
+    #[test]
+    fn batched_product() {
+        let structural = Structural {
+            b: DMatrix::from_row_slice(2, 3, &[1., 2., 3., 4., 5., 6.]),
+            c: DMatrix::from_row_slice(2, 2, &[1., -1., 0.5, 2.]),
+            w: vec![1., 10.],
+            z: vec![0.02, 0.05],
+            residual: Some(DMatrix::from_element(2, 3, 0.1)),
+            ..Default::default()
+        };
+        let jw = Complex::new(0., 3.);
+        let expected = structural
+            .c
+            .column_iter()
+            .zip(structural.b.row_iter())
+            .zip(structural.w.iter().zip(&structural.z))
+            .fold(
+                DMatrix::from_element(2, 3, Complex::new(0.1, 0.)),
+                |a, ((c, b), (wi, zi))| {
+                    let ode = wi * wi + jw * jw + 2. * zi * wi * jw;
+                    a + (c * b).map(|x| Complex::new(x, 0.)) / ode
+                },
+            );
+        let fr = structural.j_omega(jw);
+        assert!((fr - expected).norm() < 1e-12);
+    }
+
+    #[test]
+    fn hysteretic() {
+        // eigen frequency [rd/s] and loss factor
+        let (omega, eta) = (2. * consts::PI * 5., 0.04);
+        let structural = Structural {
+            b: DMatrix::from_element(1, 1, 1.),
+            c: DMatrix::from_element(1, 1, 1.),
+            w: vec![omega],
+            z: vec![eta],
+            damping_model: DampingModel::Hysteretic,
+            ..Default::default()
+        };
+        for nu in [1., omega, 100.] {
+            let expected = 1. / (omega * omega * Complex::new(1., eta) - nu * nu);
+            let fr = structural.j_omega(Complex::new(0., nu));
+            assert!((fr[(0, 0)] - expected).norm() < 1e-12);
+        }
+    }
+
+    // 1 input, 2 outputs and 3 modes with the static gain of all the modes
+    fn modal_model() -> ModalModel {
+        let mut model = ModalModel {
+            inputs: vec!["u".into()],
+            inputs_size: vec![1],
+            outputs: vec!["y".into()],
+            outputs_size: vec![2],
+            eigen_frequencies: vec![1., 5., 50.],
+            b: DMatrix::from_row_slice(3, 1, &[1., 0.5, -2.]),
+            c: DMatrix::from_row_slice(2, 3, &[1., 2., 0.5, -1., 0.3, 1.5]),
+            ..Default::default()
+        };
+        model.static_gain = Some(model.modal_static_gain());
+        model
+    }
+
+    #[test]
+    fn residual_correction() {
+        let dc = Complex::new(0., 0.);
+        let full = Structural::builder(vec![], vec![])
+            .build_from(modal_model())
+            .unwrap();
+        let truncated = || Structural::builder(vec![], vec![]).max_eigen_frequency(Some(10.));
+        let corrected = truncated()
+            .enable_residual_correction()
+            .build_from(modal_model())
+            .unwrap();
+        assert_eq!(corrected.w.len(), 2);
+        assert!((corrected.j_omega(dc) - full.j_omega(dc)).norm() < 1e-12);
+        let uncorrected = truncated().build_from(modal_model()).unwrap();
+        assert!((uncorrected.j_omega(dc) - full.j_omega(dc)).norm() > 1e-9);
+        // only the modes above the eigen frequency window are corrected
+        let above = Structural::builder(vec![], vec![])
+            .min_eigen_frequency(Some(2.))
+            .build_from(modal_model())
+            .unwrap();
+        let windowed = truncated()
+            .min_eigen_frequency(Some(2.))
+            .enable_residual_correction()
+            .build_from(modal_model())
+            .unwrap();
+        assert_eq!(windowed.w.len(), 1);
+        assert!((windowed.j_omega(dc) - above.j_omega(dc)).norm() < 1e-12);
+        assert!(matches!(
+            truncated()
+                .enable_residual_correction()
+                .enable_static_gain_mismatch_compensation(None)
+                .build(),
+            Err(StructuralError::StaticGainCorrections)
+        ));
+    }
+
+    #[test]
+    fn channels_selection() {
+        let mut builder = Structural::builder(vec!["oss-harpoint-delta-f".into()], vec![]);
+        builder.inputs_channels =
+            BTreeMap::from([("oss-harpoint-delta-f".to_string(), Channels::from(3..5))]);
+        let io = [FemIo {
+            name: "OSS_Harpoint_delta_F".into(),
+            size: 6,
+            ..Default::default()
+        }];
+        resolve_fem_names(&mut builder.built.inputs, &mut builder.inputs_channels, &io).unwrap();
+        let model = ModalModel {
+            inputs: vec!["OSS_Harpoint_delta_F".into()],
+            inputs_size: vec![6],
+            b: DMatrix::from_fn(3, 6, |i, j| (i + j) as f64),
+            static_gain: None,
+            ..modal_model()
+        };
+        let structural = builder.build_from(model).unwrap();
+        assert_eq!(structural.inputs_channels, vec![vec![3, 4]]);
+        let data = TransferFunctionData::default()
+            .add_structural(&structural)
+            .add_response(structural.try_frequency_response(1f64).unwrap());
+        let (outputs, inputs) = data.channels();
+        assert_eq!(outputs[1], ("y".to_string(), 1));
+        assert_eq!(
+            inputs,
+            vec![
+                ("OSS_Harpoint_delta_F".to_string(), 3),
+                ("OSS_Harpoint_delta_F".to_string(), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn eigen_window() {
+        let builder = Structural::builder(vec![], vec![])
+            .min_eigen_frequency(Some(10.))
+            .max_eigen_frequency(Some(1.));
+        assert!(matches!(
+            builder.build(),
+            Err(StructuralError::EigenWindow { .. })
+        ));
+        assert!(
+            Structural::default()
+                .to_string()
+                .contains("eigen frequencies: none")
+        );
+    }
+
     #[test]
     fn mount() {
         let structural = Structural::builder(
@@ -330,7 +970,7 @@ mod tests {
         .build()
         .unwrap();
 
-        let tf = structural.frequency_response(1f64);
+        let tf = structural.try_frequency_response(1f64).unwrap();
         println!("{}", tf[0]);
     }
 
@@ -343,7 +983,9 @@ mod tests {
         .build()
         .unwrap();
 
-        let tf = structural.frequency_response(Frequencies::logspace(0.1, 100., 1000));
+        let tf = structural
+            .try_frequency_response(Frequencies::logspace(0.1, 100., 1000))
+            .unwrap();
         println!("{}", tf[0]);
 
         // let mut file = File::create("mount_el_tf.pkl").unwrap();
@@ -356,11 +998,13 @@ mod tests {
             vec!["OSS_ElDrive_Torque".to_string()],
             vec!["OSS_ElEncoder_Angle".to_string()],
         )
-        // .enable_static_gain_mismatch_compensation(Some(1. / 8e3))
+        .enable_static_gain_mismatch_compensation(Some(1. / 8e3))
         .build()
         .unwrap();
 
-        let tf = structural.frequency_response(Frequencies::logspace(0.1, 4e3, 1000));
+        let tf = structural
+            .try_frequency_response(Frequencies::logspace(0.1, 4e3, 1000))
+            .unwrap();
         //println!("{:?}", nu);
         println!("{}", tf[0]);
 
@@ -377,11 +1021,13 @@ mod tests {
         .build()
         .unwrap();
 
-        let tf = structural.frequency_response(Frequencies::LinSpace {
-            lower: 1f64,
-            upper: 10f64,
-            n: 2,
-        });
+        let tf = structural
+            .try_frequency_response(Frequencies::LinSpace {
+                lower: 1f64,
+                upper: 10f64,
+                n: 2,
+            })
+            .unwrap();
         // println!("{:?}", nu);
         println!("{}", tf[0]);
 
@@ -0,0 +1,45 @@
+//! Text tables parsing
+//!
+//! Tables are either comma or white space separated values,
+//! empty lines and lines starting with `#` are skipped and
+//! the first line is treated as a header if it cannot be parsed
+
+use std::{fs, io, num::ParseFloatError, path::Path};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TableError {
+    #[error("failed to read table file")]
+    Read(#[from] io::Error),
+    #[error("failed to parse line #{0} of table file")]
+    Parse(usize, #[source] ParseFloatError),
+}
+
+/// Reads the rows of a text table
+pub(crate) fn read_rows(path: impl AsRef<Path>) -> Result<Vec<Vec<f64>>, TableError> {
+    let contents = fs::read_to_string(path)?;
+    let mut rows = vec![];
+    let mut header = true;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let row = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>();
+        match row {
+            Ok(row) => rows.push(row),
+            Err(_) if header => (),
+            Err(e) => return Err(TableError::Parse(i + 1, e)),
+        }
+        header = false;
+    }
+    Ok(rows)
+}
+
+/// Reads all the values of a text table, row after row
+pub(crate) fn read_values(path: impl AsRef<Path>) -> Result<Vec<f64>, TableError> {
+    Ok(read_rows(path)?.into_iter().flatten().collect())
+}
@@ -0,0 +1,220 @@
+//! Tabular data files
+//!
+//! The frequency response is written in the long format, one row per frequency
+//! and per output and input channels pair, to either an Apache Parquet or an Arrow IPC file.
+//! The table columns are:
+//!  * `frequency`: the frequency,
+//!  * `output` and `output_channel`: the FEM output name and channel index,
+//!  * `input` and `input_channel`: the FEM input name and channel index,
+//!  * `magnitude` and `phase`: the frequency response magnitude and phase,
+//!  * `re` and `im`: the frequency response real and imaginary parts.
+//!
+//! The table is queried with DataFrame tools, e.g. in Python with polars
+//! ```python
+//! import polars as pl
+//! tf = pl.scan_parquet("gmt_frequency_response.parquet")
+//! tf.filter((pl.col("output") == "OSS_M1_lcl") & (pl.col("input_channel") == 0)).collect()
+//! ```
+
+use std::{collections::HashMap, fs::File, io, path::Path, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, RecordBatch,
+    builder::{ArrayBuilder, Float64Builder, StringBuilder, UInt64Builder},
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter, basic::Compression, errors::ParquetError,
+    file::properties::WriterProperties,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TabularError {
+    #[error("failed to create tabular data file")]
+    IO(#[from] io::Error),
+    #[error("failed to write Arrow IPC data file")]
+    Arrow(#[from] ArrowError),
+    #[error("failed to write Parquet data file")]
+    Parquet(#[from] ParquetError),
+}
+type Result<T> = std::result::Result<T, TabularError>;
+
+/// Number of rows of the record batches
+const BATCH_SIZE: usize = 1 << 16;
+
+/// Tabular data file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableFormat {
+    /// Apache Parquet file
+    Parquet,
+    /// Arrow IPC file
+    ArrowIpc,
+}
+
+/// Table row: frequency response of an output channel to an input channel at a frequency
+pub(crate) struct Row<'a> {
+    pub(crate) frequency: f64,
+    pub(crate) output: &'a str,
+    pub(crate) output_channel: usize,
+    pub(crate) input: &'a str,
+    pub(crate) input_channel: usize,
+    pub(crate) magnitude: f64,
+    pub(crate) phase: f64,
+    pub(crate) re: f64,
+    pub(crate) im: f64,
+}
+
+/// Table columns builders
+#[derive(Default)]
+struct Columns {
+    frequency: Float64Builder,
+    output: StringBuilder,
+    output_channel: UInt64Builder,
+    input: StringBuilder,
+    input_channel: UInt64Builder,
+    magnitude: Float64Builder,
+    phase: Float64Builder,
+    re: Float64Builder,
+    im: Float64Builder,
+}
+impl Columns {
+    fn schema(metadata: HashMap<String, String>) -> SchemaRef {
+        let float = |name: &str| Field::new(name, DataType::Float64, false);
+        Arc::new(
+            Schema::new(vec![
+                float("frequency"),
+                Field::new("output", DataType::Utf8, false),
+                Field::new("output_channel", DataType::UInt64, false),
+                Field::new("input", DataType::Utf8, false),
+                Field::new("input_channel", DataType::UInt64, false),
+                float("magnitude"),
+                float("phase"),
+                float("re"),
+                float("im"),
+            ])
+            .with_metadata(metadata),
+        )
+    }
+    fn append(&mut self, row: &Row) {
+        self.frequency.append_value(row.frequency);
+        self.output.append_value(row.output);
+        self.output_channel.append_value(row.output_channel as u64);
+        self.input.append_value(row.input);
+        self.input_channel.append_value(row.input_channel as u64);
+        self.magnitude.append_value(row.magnitude);
+        self.phase.append_value(row.phase);
+        self.re.append_value(row.re);
+        self.im.append_value(row.im);
+    }
+    fn len(&self) -> usize {
+        self.frequency.len()
+    }
+    /// Returns the record batch of the appended rows and resets the builders
+    fn finish(&mut self, schema: &SchemaRef) -> Result<RecordBatch> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.frequency.finish()),
+            Arc::new(self.output.finish()),
+            Arc::new(self.output_channel.finish()),
+            Arc::new(self.input.finish()),
+            Arc::new(self.input_channel.finish()),
+            Arc::new(self.magnitude.finish()),
+            Arc::new(self.phase.finish()),
+            Arc::new(self.re.finish()),
+            Arc::new(self.im.finish()),
+        ];
+        Ok(RecordBatch::try_new(schema.clone(), columns)?)
+    }
+}
+
+/// Parquet or Arrow IPC file writer
+enum Writer {
+    Parquet(ArrowWriter<File>),
+    ArrowIpc(arrow_ipc::writer::FileWriter<File>),
+}
+impl Writer {
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            Self::Parquet(writer) => writer.write(batch)?,
+            Self::ArrowIpc(writer) => writer.write(batch)?,
+        }
+        Ok(())
+    }
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Parquet(writer) => {
+                writer.close()?;
+            }
+            Self::ArrowIpc(mut writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+/// Writes the rows to a table file with the key-value `metadata` added to the table schema
+///
+/// The rows are written by record batches of [BATCH_SIZE] rows
+pub(crate) fn write<'a>(
+    path: impl AsRef<Path>,
+    format: TableFormat,
+    metadata: HashMap<String, String>,
+    rows: impl IntoIterator<Item = Row<'a>>,
+) -> Result<()> {
+    let schema = Columns::schema(metadata);
+    let file = File::create(path)?;
+    let mut writer = match format {
+        TableFormat::Parquet => Writer::Parquet(ArrowWriter::try_new(
+            file,
+            schema.clone(),
+            Some(
+                WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build(),
+            ),
+        )?),
+        TableFormat::ArrowIpc => {
+            Writer::ArrowIpc(arrow_ipc::writer::FileWriter::try_new(file, &schema)?)
+        }
+    };
+    let mut columns = Columns::default();
+    for row in rows {
+        columns.append(&row);
+        if columns.len() == BATCH_SIZE {
+            writer.write(&columns.finish(&schema)?)?;
+        }
+    }
+    if columns.len() > 0 {
+        writer.write(&columns.finish(&schema)?)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempFile;
+
+    #[test]
+    fn arrow_ipc() {
+        let path = TempFile::new("arrow_ipc.arrow");
+        let rows = (0..3).map(|k| Row {
+            frequency: k as f64,
+            output: "OSS_M1_lcl",
+            output_channel: k,
+            input: "OSS_Harpoint_delta_F",
+            input_channel: 0,
+            magnitude: 1.,
+            phase: 0.,
+            re: 1.,
+            im: 0.,
+        });
+        let metadata = HashMap::from([("frequency_unit".to_string(), "Hz".to_string())]);
+        write(&path, TableFormat::ArrowIpc, metadata, rows).unwrap();
+        let reader =
+            arrow_ipc::reader::FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        assert_eq!(reader.schema().metadata()["frequency_unit"], "Hz");
+        let batches: Vec<_> = reader.collect::<std::result::Result<_, _>>().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 3);
+        assert_eq!(batches[0].num_columns(), 9);
+    }
+}
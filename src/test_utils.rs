@@ -0,0 +1,24 @@
+//! Tests helpers
+
+use std::path::{Path, PathBuf};
+
+/// Temporary file, unique to a test, removed when dropped even if the test fails
+pub(crate) struct TempFile(PathBuf);
+impl TempFile {
+    pub(crate) fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!(
+            "gmt-fem-frequency-response_{}_{name}",
+            std::process::id()
+        )))
+    }
+}
+impl AsRef<Path> for TempFile {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
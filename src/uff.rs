@@ -0,0 +1,187 @@
+//! Universal File Format
+//!
+//! The frequency response functions are written to an ASCII Universal File Format (UFF) file
+//! as datasets 58, one dataset per output and input channels pair.
+//! The response and the reference entities are the FEM output and input names,
+//! truncated to 10 characters, and the node numbers are the channels indices plus one,
+//! offset by the largest node number of the previous outputs or inputs (see [node_numbers])
+//! so that the nodes are unique even if the names are the same once truncated.
+//! The function ordinates are double precision complex numbers at uneven abscissa in Hz.
+
+use std::fmt::Display;
+
+use num_complex::Complex;
+
+/// UFF dataset delimiter
+const DELIMITER: i32 = -1;
+/// UFF function type of a frequency response function
+const FREQUENCY_RESPONSE_FUNCTION: i32 = 4;
+/// UFF ordinate data type of double precision complex numbers
+const COMPLEX_DOUBLE: i32 = 6;
+/// UFF specific data type of a frequency axis
+const FREQUENCY: i32 = 18;
+
+/// Frequency response function of an output channel to an input channel, written as a dataset 58
+pub(crate) struct FrequencyResponseFunction<'a> {
+    /// function identification number
+    pub(crate) id: usize,
+    /// FEM name
+    pub(crate) fem: &'a str,
+    /// FEM output name, channel index and node number
+    pub(crate) response: (&'a str, usize, usize),
+    /// FEM input name, channel index and node number
+    pub(crate) reference: (&'a str, usize, usize),
+    /// frequencies \[Hz\]
+    pub(crate) frequencies: &'a [f64],
+    /// complex frequency response at each frequency
+    pub(crate) values: Vec<Complex<f64>>,
+}
+impl Display for FrequencyResponseFunction<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (response, response_channel, response_node) = self.response;
+        let (reference, reference_channel, reference_node) = self.reference;
+        writeln!(f, "{DELIMITER:>6}")?;
+        writeln!(f, "{:>6}", 58)?;
+        // records 1-5: identification lines
+        for id in [
+            format!("{response}[{response_channel}] / {reference}[{reference_channel}]"),
+            format!("GMT FEM {}", self.fem),
+            "NONE".to_string(),
+            "NONE".to_string(),
+            "NONE".to_string(),
+        ] {
+            writeln!(f, "{}", truncate(&id, 80))?;
+        }
+        // record 6: DOF identification
+        writeln!(
+            f,
+            "{:>5}{:>10}{:>5}{:>10} {:<10}{:>10}{:>4} {:<10}{:>10}{:>4}",
+            FREQUENCY_RESPONSE_FUNCTION,
+            self.id,
+            0,
+            0,
+            truncate(response, 10),
+            response_node,
+            0,
+            truncate(reference, 10),
+            reference_node,
+            0
+        )?;
+        // record 7: data form
+        writeln!(
+            f,
+            "{:>10}{:>10}{:>10}{}{}{}",
+            COMPLEX_DOUBLE,
+            self.values.len(),
+            0,
+            fortran_e(0., 13, 5),
+            fortran_e(0., 13, 5),
+            fortran_e(0., 13, 5)
+        )?;
+        // records 8-11: abscissa, ordinate numerator, ordinate denominator and z-axis characteristics
+        for (data_type, label, unit) in [
+            (FREQUENCY, "Frequency", "Hz"),
+            (0, "Response", "NONE"),
+            (0, "Reference", "NONE"),
+            (0, "NONE", "NONE"),
+        ] {
+            writeln!(
+                f,
+                "{:>10}{:>5}{:>5}{:>5} {:<20} {:<20}",
+                data_type, 0, 0, 0, label, unit
+            )?;
+        }
+        // record 12: data values
+        for (nu, value) in self.frequencies.iter().zip(&self.values) {
+            writeln!(
+                f,
+                "{}{}{}",
+                fortran_e(*nu, 13, 5),
+                fortran_e(value.re, 20, 12),
+                fortran_e(value.im, 20, 12)
+            )?;
+        }
+        writeln!(f, "{DELIMITER:>6}")
+    }
+}
+
+/// Returns the node numbers of the `(name, channel index)` channels of the outputs or the inputs
+///
+/// The node number of a channel is its index plus one, offset by the largest node number
+/// of the previous outputs or inputs
+pub(crate) fn node_numbers(channels: &[(String, usize)]) -> Vec<usize> {
+    let mut nodes = Vec::with_capacity(channels.len());
+    let (mut offset, mut last) = (0, 0);
+    let mut previous: Option<&str> = None;
+    for (name, channel) in channels {
+        if previous != Some(name.as_str()) {
+            offset = last;
+            previous = Some(name);
+        }
+        let node = offset + channel + 1;
+        last = last.max(node);
+        nodes.push(node);
+    }
+    nodes
+}
+
+/// Returns at most the first `n` characters of `s`
+fn truncate(s: &str, n: usize) -> &str {
+    s.char_indices().nth(n).map_or(s, |(i, _)| &s[..i])
+}
+
+/// Formats `x` in the Fortran `Ew.d` notation, e.g. `  1.23450E+02` for `E13.5`
+fn fortran_e(x: f64, w: usize, d: usize) -> String {
+    let e = format!("{x:.d$E}");
+    match e.split_once('E').map(|(m, e)| (m, e.parse::<i32>())) {
+        Some((mantissa, Ok(exponent))) => format!("{:>w$}", format!("{mantissa}E{exponent:+03}")),
+        _ => format!("{e:>w$}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dataset() {
+        assert_eq!(fortran_e(123.45, 13, 5), "  1.23450E+02");
+        assert_eq!(fortran_e(-1.5e-12, 13, 5), " -1.50000E-12");
+        let frf = FrequencyResponseFunction {
+            id: 1,
+            fem: "20250506_1715_zen_30_M1_202110_FSM_202305_Mount_202305_pier_202411",
+            response: ("OSS_M1_lcl_with_a_long_name", 41, 42),
+            reference: ("OSS_M1_lcl_6F", 0, 1),
+            frequencies: &[1., 2.],
+            values: vec![Complex::new(1., -1.), Complex::new(0.5, 0.25)],
+        }
+        .to_string();
+        let lines: Vec<_> = frf.lines().collect();
+        assert_eq!(lines.len(), 2 + 5 + 1 + 1 + 4 + 2 + 1);
+        assert_eq!(lines[..2], ["    -1", "    58"]);
+        assert_eq!(
+            lines[2],
+            "OSS_M1_lcl_with_a_long_name[41] / OSS_M1_lcl_6F[0]"
+        );
+        assert_eq!(
+            lines[7],
+            "    4         1    0         0 OSS_M1_lcl        42   0 OSS_M1_lcl         1   0"
+        );
+        assert!(lines[8].starts_with("         6         2         0"));
+        assert_eq!(
+            lines[13],
+            "  1.00000E+00  1.000000000000E+00 -1.000000000000E+00"
+        );
+        assert_eq!(lines[15], "    -1");
+    }
+
+    #[test]
+    fn nodes() {
+        let channels: Vec<_> = [("OSS_M1_lcl", 6), ("OSS_M1_lcl", 7)]
+            .into_iter()
+            .chain((0..2).map(|k| ("OSS_M1_lcl_6F", k)))
+            .map(|(name, k)| (name.to_string(), k))
+            .collect();
+        assert_eq!(node_numbers(&channels), vec![7, 8, 9, 10]);
+    }
+}